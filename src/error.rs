@@ -0,0 +1,119 @@
+use std::{num::ParseFloatError, string::FromUtf8Error};
+
+/// Errors of modifying trains. Messages of the errors causing them are in their `source`, so printing the whole chain gives the full story.
+#[derive(Debug, thiserror::Error)]
+pub enum ModifyError {
+    #[error("no tag `{0}`")]
+    MissingTag(&'static str),
+    /// Like `MissingTag`, when looking for the acceleration and the consist, whose errors quote names in single quotes.
+    #[error("no tag '{0}'")]
+    MissingConsistTag(&'static str),
+    #[error("tag 'FahrzeugInfo' has no tag 'Datei'")]
+    MissingVehicleFile,
+    #[error("tag 'Datei' inside tag 'FahrzeugInfo' has no attribute 'Dateiname'")]
+    MissingVehicleFileName,
+    #[error("no attribute `{attribute}` on `{tag}`")]
+    MissingAttribute {
+        attribute: &'static str,
+        tag: &'static str,
+    },
+    #[error("no attribute 'APBeschl'")]
+    MissingAcceleration,
+    /// A `.trn` file describes a single train, so only the first of multiple tags would be modified.
+    #[error("{count} tags `{tag}` inside `{inside}`, expected one")]
+    MultipleTags {
+        tag: &'static str,
+        inside: &'static str,
+        count: usize,
+    },
+    #[error("no `FahrplanEintrag` entry inside `Zug`")]
+    MissingEntry,
+    /// The train has neither `Ank` nor `Abf` on its first `FahrplanEintrag`.
+    #[error("no starting time: no attribute `Ank` or `Abf` on first `FahrplanEintrag`")]
+    MissingEntryTime,
+    /// The timetable has no `FplZeile` with a time to delay.
+    #[error("no `FplZeile` with tag `{tag}` inside `Buchfahrplan`")]
+    MissingTimetableEntry { tag: &'static str },
+    /// `what` is the kind of time, like `arrival time`.
+    #[error("parsing {what} `{time}`")]
+    ParseTime {
+        what: &'static str,
+        time: String,
+        #[source]
+        source: chrono::ParseError,
+    },
+    #[error("unable to parse `{attribute}` `{value}`")]
+    ParseNumber {
+        attribute: &'static str,
+        value: String,
+        #[source]
+        source: ParseFloatError,
+    },
+    /// A delay moved a time, of the kind given like for `ParseTime`, out of the range of dates.
+    #[error("calculating new {0}")]
+    TimeOutOfRange(&'static str),
+    /// A file name of the consist contains `lok`, but is not in a known locomotive folder.
+    #[error("unable to determine whether '{0}' is a locomotive, use a loc pattern to decide")]
+    NoLocomotiveInfo(String),
+    /// A tag in the consist that ZuSi may have added in a newer version.
+    #[error("Unknown tag '{0}' inside tag 'FahrzeugVarianten' or 'FahrzeugInfo'")]
+    UnknownConsistTag(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    ParseXml(#[from] xmltree::ParseError),
+    #[error(transparent)]
+    WriteXml(#[from] xmltree::Error),
+    /// The file is not a ZuSi 3 file, like those of ZuSi 2.
+    #[error("unsupported file: {0}")]
+    UnsupportedFormat(String),
+    /// The modified file contains characters its encoding, named here, cannot represent.
+    #[error("modified file contains characters that can not be written in {0}")]
+    Unencodable(&'static str),
+    #[error("writer produced invalid UTF-8")]
+    InvalidUtf8(#[from] FromUtf8Error),
+    /// What was being done when `source` occurred.
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<ModifyError>,
+    },
+}
+
+impl ModifyError {
+    /// The error that caused this one, skipping the contexts around it.
+    pub fn kind(&self) -> &ModifyError {
+        match self {
+            ModifyError::Context { source, .. } => source.kind(),
+            error => error,
+        }
+    }
+}
+
+pub type Result<T, E = ModifyError> = std::result::Result<T, E>;
+
+/// Adds a context to errors, like `anyhow::Context` does.
+pub(crate) trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T, E: Into<ModifyError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|source| ModifyError::Context {
+            context: context.into(),
+            source: Box::new(source.into()),
+        })
+    }
+
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|source| ModifyError::Context {
+            context: context(),
+            source: Box::new(source.into()),
+        })
+    }
+}
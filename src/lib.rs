@@ -0,0 +1,2692 @@
+//! Modifies ZuSi timetables, like the `zusischewe` command does.
+//!
+//! The functions working on a tree modify an already parsed `.trn` file, `read_file` and `write_file` parse and write one like ZuSi does, and `modify_file` does all of that according to `ModifyParams`.
+//!
+//! ```
+//! use std::collections::HashMap;
+//!
+//! use zusischewe::xmltree::Element;
+//!
+//! let mut tree = Element::parse(
+//!     r#"<Zusi>
+//!         <Zug APBeschl="0.8" Zugtyp="1">
+//!             <FahrplanEintrag Ank="2016-05-20 06:15:00" Abf="2016-05-20 06:16:00"/>
+//!             <FahrzeugVarianten>
+//!                 <FahrzeugInfo><Datei Dateiname="RollingStock\Deutschland\Elektroloks\101.rv.fzg"/></FahrzeugInfo>
+//!             </FahrzeugVarianten>
+//!         </Zug>
+//!     </Zusi>"#
+//!         .as_bytes(),
+//! )?;
+//!
+//! let has_locomotive = zusischewe::train_has_locomotive(&tree, None, &[], None)?;
+//! assert_eq!(has_locomotive, Some(true));
+//!
+//! zusischewe::modify_multiplier(&mut tree, true, 0.5, 0.8, &HashMap::new(), None, None)?;
+//!
+//! zusischewe::delay_entry(&mut tree, 90, 0)?;
+//!
+//! let zug = tree.get_child("Zug").unwrap();
+//! assert_eq!(zug.attributes["APBeschl"], "0.4");
+//! assert_eq!(
+//!     zug.get_child("FahrplanEintrag").unwrap().attributes["Ank"],
+//!     "2016-05-20 06:16:30"
+//! );
+//! # Ok::<(), zusischewe::ModifyError>(())
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::{self, File},
+    io::Write as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::Parser;
+use encoding_rs::Encoding;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Distribution;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+pub use error::{ModifyError, Result};
+pub use xmltree;
+
+use error::Context;
+
+mod error;
+
+/// Folder inside the modified folder that cancelled trains are moved to.
+pub const CANCELLED_FOLDER: &str = "_cancelled";
+
+/// Attribute of `Zug` keeping its `APBeschl` from before the first modification.
+pub const ORIGINAL_ACCELERATION: &str = "APBeschlOrig";
+
+/// Format of the `Ank` and `Abf` attributes.
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Format of times without seconds, which some files use instead of `TIME_FORMAT`.
+pub const MINUTE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How `modify_file` modifies a train. The defaults are those of the `modify` command, with which nothing is changed.
+#[derive(Debug, Clone, clap::Args, Serialize)]
+pub struct ModifyParams {
+    /// Multiply the acceleration/deceleration of all trains by this factor.
+    ///
+    /// This affects the `APBeschl` property of trains.
+    #[arg(short = 'm', long)]
+    pub multiplier: Option<f32>,
+    /// Reduce the acceleration/deceleration of all trains by this percentage, like 20 for 80% of the original `APBeschl`.
+    ///
+    /// Replaces the friction model: the friction, the friction needed, the ratio model and the weather ramp are ignored, and the acceleration of all trains is multiplied by 1 - p/100. Type multipliers, the speed penalty and the minimum acceleration still apply on top.
+    #[arg(long, conflicts_with = "multiplier")]
+    pub accel_reduction_percent: Option<f32>,
+
+    /// Modify train acceleration/deceleration assuming this is the coeffient of friction.
+    ///
+    /// This affects the `APBeschl` property of trains.
+    ///
+    /// The new `APBeschl` of the train is A*min(μ/M, 1) where Α is the old `APBeschl` value, μ is the new coefficient of friction, M is the coefficient of friction needed for the train to achieve full acceleration (see arguments loc_needed and mu_needed).
+    #[arg(short = 'f', long, default_value = "0.4")]
+    pub friction: f32,
+    /// Coefficient of friction needed for locomotives to achieve full acceleration/deceleration.
+    ///
+    /// See the help of the friction argument for details.
+    #[arg(short = 'l', long, default_value = "0.4")]
+    pub loc_needed: f32,
+    /// Coefficient of friction needed for multiple units to achieve full acceleration/deceleration.
+    ///
+    /// See the help of the friction argument for details.
+    #[arg(short = 't', long, default_value = "0.25")]
+    pub mu_needed: f32,
+    /// Interpolate the friction needed between the locomotive and multiple unit values by the share of locomotives in the consist.
+    ///
+    /// By default, any locomotive makes a train use the locomotive value. With this, a light engine, which is powered throughout like a multiple unit, uses the multiple unit value, and the locomotive value is approached the more unpowered vehicles it hauls. Trains without a locomotive still use the multiple unit value.
+    #[arg(long, action)]
+    pub ratio_model: bool,
+    /// Minimum `APBeschl` of trains after modification, in the units of `APBeschl`.
+    ///
+    /// Keeps trains able to climb gradients. Overrides the friction model and the multiplier where their result would be lower. Trains whose original `APBeschl` is lower keep it.
+    #[arg(visible_alias = "mina", long)]
+    pub min_acceleration: Option<f32>,
+    /// Multiply the reduction of the acceleration of fast trains by this factor. Passing this argument applies the speed penalty.
+    ///
+    /// A train is fast if its top speed, the `spZugNiedriger` attribute, is above the speed threshold. With a penalty of 2, a fast train whose acceleration would be reduced to 80% is reduced to 60% instead. Trains without the attribute use the friction model as usual. The multiplier counts towards the reduction.
+    #[arg(visible_alias = "sp", long)]
+    pub speed_penalty: Option<f32>,
+    /// Top speed in km/h above which the speed penalty applies.
+    #[arg(visible_alias = "st", long, default_value = "160")]
+    pub speed_threshold: f32,
+    /// Fail on tags in the consist that are not known, instead of treating them as no locomotive.
+    ///
+    /// Newer ZuSi versions may add tags to the consist. By default, unknown tags are reported after modifying.
+    #[arg(long, action)]
+    pub strict: bool,
+    /// Do not modify the acceleration of trains if it is unclear whether they have a locomotive, instead of treating them as having none.
+    ///
+    /// This is the case for trains without a locomotive whose consist is empty or has unknown tags. They are reported after modifying.
+    #[arg(long, action)]
+    pub no_multiplier_if_no_loco_info: bool,
+    /// Regex deciding which vehicles are locomotives, matched against the `Dateiname` of the vehicle.
+    ///
+    /// By default, a vehicle is a locomotive if a folder in its path is named like `Elektroloks`, `Dieselloks` or `Lokomotiven` (case-insensitive). Vehicles that only contain "lok" elsewhere in their path are reported as errors, as they can not be classified reliably. Passing this argument replaces the default detection.
+    #[arg(long)]
+    #[serde(serialize_with = "serialize_pattern")]
+    pub loc_pattern: Option<Regex>,
+    /// Vehicles whose `Dateiname` contains this, ignoring case, are locomotives, like `tfz` or `triebkopf`. Can be given multiple times.
+    ///
+    /// This extends the default detection for libraries naming their locomotives differently. Vehicles containing "lok" without being in a locomotive folder are still reported, unless they contain one of these.
+    #[arg(long, conflicts_with = "loc_pattern")]
+    pub locomotive_substring: Vec<String>,
+
+    /// Delay type A: probability of delay. Passing this argument applies delay type A.
+    ///
+    /// Delay type A delays the entry of trains by A(exp(μr)-1) where A is the amplitude and r is a random real in the interval [0, 1).
+    #[arg(visible_alias = "dp", long)]
+    pub delay_probability: Option<f32>,
+    /// Delay type A: amplitude of delay.
+    #[arg(visible_alias = "da", long, default_value = "360")]
+    pub delay_amplitude: f32,
+    /// Delay type A: λ parameter of delay.
+    #[arg(visible_alias = "dl", long, default_value = "3")]
+    pub delay_lambda: f32,
+
+    /// Delay type B: mean delay in minutes, or the delay unit. Passing this argument applies delay type B.
+    ///
+    /// Delay type B delays the entry of trains according to a normal distribution.
+    #[arg(visible_alias = "bm", long)]
+    pub bell_mean: Option<f32>,
+    /// Delay type B: stardard deviation of delay in minutes, or the delay unit.
+    #[arg(visible_alias = "bd", long, default_value = "5")]
+    pub bell_deviation: f32,
+    /// Delay type B: skew of the distribution. Positive values make late entries more likely than early ones.
+    ///
+    /// A bias other than 0 uses a skew normal distribution, of which the mean and deviation are the location and scale. Its mean is then larger than the mean argument for positive biases. 0 is the normal distribution.
+    #[arg(long, default_value = "0", allow_hyphen_values = true)]
+    pub delay_bias: f32,
+
+    /// Delay type C: shape (k) of the gamma distribution. Passing this argument and the scale applies delay type C.
+    ///
+    /// Delay type C delays the entry of trains according to a gamma distribution in minutes. It is never negative and has a long right tail, like real operational delays. The mean delay is shape * scale.
+    #[arg(visible_alias = "gk", long, requires = "gamma_scale")]
+    pub gamma_shape: Option<f32>,
+    /// Delay type C: scale (θ) of the gamma distribution in minutes, or the delay unit.
+    #[arg(visible_alias = "gt", long, requires = "gamma_shape")]
+    pub gamma_scale: Option<f32>,
+
+    /// Only apply these delay types, like `a,c`. Defaults to all of them.
+    ///
+    /// Delay types only apply if the arguments they need are given, so this allows keeping their arguments while turning them off. The delays of all applied types are summed.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub delay_types: Vec<DelayType>,
+    /// Unit of the arguments of the delay types, the advance and the minimum and maximum entry delay.
+    ///
+    /// Seconds avoid fractional minutes for short delays, like `--da 30` instead of `--da 0.5`.
+    #[arg(long, value_enum, default_value = "minutes")]
+    pub delay_unit: DelayUnit,
+
+    /// Delay all stops of the train by the entry delay, not only the entry.
+    ///
+    /// Times are never moved before the preceding time of the train, so departures never precede arrivals.
+    #[arg(visible_alias = "pd", long, action)]
+    pub propagate_entry_delay: bool,
+    /// Note the entry delay in the route of the train, like `München Hbf - Amsterdam CS (+5 min)`.
+    ///
+    /// The route (`Zuglauf`) is what ZuSi shows when choosing a train, so the delay is known before driving. The times are still delayed. Delays of less than half a minute are not noted.
+    #[arg(long, visible_alias = "entry-delay-column", action)]
+    pub entry_delay_note: bool,
+    /// Seconds of the propagated entry delay the train recovers at each stop.
+    #[arg(long, default_value = "0", requires = "propagate_entry_delay")]
+    pub recovery_per_stop: u32,
+
+    /// Time of day window, like `22:00-05:00`, in which entering trains are not modified at all. Can be given multiple times.
+    ///
+    /// Like windows of peaks, these are matched against the entry time of trains and wrap around midnight if they end before they start. Trains that are skipped are still backed up and reset.
+    #[arg(long)]
+    pub skip_window: Vec<TimeWindow>,
+    /// Start of the simulated session, like `2016-05-20 07:00`. Trains entering before it are not modified at all.
+    ///
+    /// These trains are already running when the session starts, so their entry can not be delayed any more. Trains entering later may still be moved before the start by an advance, unless deny early is given.
+    #[arg(long, value_parser = parse_date_time)]
+    pub sim_start: Option<chrono::NaiveDateTime>,
+
+    /// Time of day window of a peak, like `07:00-09:00`. Can be given multiple times.
+    ///
+    /// The entry delay of trains entering during a peak is multiplied by the peak multiplier. Windows ending before they start wrap around midnight.
+    #[arg(visible_alias = "pw", long)]
+    pub peak_window: Vec<TimeWindow>,
+    /// Multiplier of the entry delay of trains entering during a peak window.
+    #[arg(visible_alias = "pm", long, default_value = "1.5")]
+    pub peak_multiplier: f32,
+
+    /// Time of day the weather starts getting worse, like `13:00`. Passing this argument and the ramp peak ramps the weather.
+    ///
+    /// Trains entering before the ramp start get neither entry delays of the delay types nor a lower acceleration from the friction. Between ramp start and ramp peak, both grow linearly to their full amount. Ramps do not wrap around midnight.
+    #[arg(long, value_parser = parse_time_of_day, requires = "ramp_peak")]
+    pub ramp_start: Option<chrono::NaiveTime>,
+    /// Time of day the weather is at its worst, see the ramp start.
+    #[arg(long, value_parser = parse_time_of_day, requires = "ramp_start")]
+    pub ramp_peak: Option<chrono::NaiveTime>,
+    /// Time of day the weather is back to normal, ramping down linearly from the ramp peak. Without it, the weather stays at its worst.
+    #[arg(long, value_parser = parse_time_of_day, requires = "ramp_peak")]
+    pub ramp_end: Option<chrono::NaiveTime>,
+
+    /// Probability of cancelling a train.
+    ///
+    /// ZuSi has no attribute for cancelled trains, so the files of cancelled trains are moved into the `_cancelled` folder inside the modified folder, where ZuSi does not find them. Reset restores them. Cancelled trains are not modified otherwise.
+    #[arg(visible_alias = "cp", long)]
+    pub cancel_probability: Option<f32>,
+
+    /// Let trains enter this many minutes, or units of the delay unit, earlier, with the advance probability.
+    ///
+    /// The advance is subtracted from the entry delay after the delay types and peak windows, so it is vetoed by denying early entries and limited by the minimum entry delay.
+    #[arg(long)]
+    pub advance: Option<f32>,
+    /// Probability of advancing the entry of a train by the advance.
+    #[arg(long, default_value = "1")]
+    pub advance_probability: f32,
+
+    /// Do not let the train enter early.
+    ///
+    /// This also keeps trains entering after the sim start from being moved before it.
+    #[arg(short, long, action)]
+    pub deny_early: bool,
+    /// Minimum entry delay in minutes, or the delay unit.
+    ///
+    /// The entry delay is the sum of all delay types, with a negative sum meaning an early entry. Denying early entries is applied first, then this minimum, then the maximum. A negative minimum therefore has no effect when early entries are denied.
+    #[arg(visible_alias = "mind", long, allow_hyphen_values = true)]
+    pub min_entry_delay: Option<f32>,
+    /// Maximum entry delay in minutes, see the minimum entry delay.
+    #[arg(visible_alias = "maxd", long, allow_hyphen_values = true)]
+    pub max_entry_delay: Option<f32>,
+    /// Maximum entry delay as a fraction of the journey time of the train, like 0.2.
+    ///
+    /// The journey time is the time from its first to its last stop. Applied after the maximum entry delay, so the smaller of both limits applies.
+    #[arg(long)]
+    pub max_delay_fraction: Option<f32>,
+
+    /// Delay trains as if passengers took a constant factor times longer to board.
+    ///
+    /// Only applies to passenger trains (`Zugtyp` 1), see the freight departures delay factor for other trains.
+    #[arg(visible_alias = "dfac", long, default_value = "1")]
+    pub departures_delay_factor: f32,
+    /// Departures delay factor of freight trains, i.e. trains that are not passenger trains.
+    ///
+    /// Station factors do not apply to freight trains.
+    #[arg(visible_alias = "ffac", long, default_value = "1")]
+    pub freight_departures_delay_factor: f32,
+    /// Maximum delay of non-entry departures in minutes.
+    #[arg(visible_alias = "dmd", long, default_value = "6")]
+    pub departures_max_delay: f32,
+    /// Seconds added to the delay of each departure of passenger trains, on top of the departures delay factor.
+    ///
+    /// Models doors taking longer to open and close, which also delays short stops. The sum is still limited by the departures max delay.
+    #[arg(long, default_value = "0")]
+    pub min_added_dwell: u32,
+    /// Do not delay the departure at the last stop of trains, where passengers only alight.
+    #[arg(long, action)]
+    pub no_terminus_dwell: bool,
+    /// Move each departure by a random offset of up to this many seconds, earlier or later, on top of the departures delay.
+    ///
+    /// Models the irregularities of dispatching. The offset is drawn per station (`Betrst`), departures at stops without a station are not moved. Departures are never moved before their arrival, and only later when early entries are denied.
+    #[arg(long, default_value = "0")]
+    pub departure_jitter: u32,
+    /// Probability of delaying the departure at a station, drawn for each station of each train.
+    ///
+    /// Departures not drawn keep their dwell. Stops without a station (`Betrst`) are always delayed.
+    #[arg(visible_alias = "dprob", long, default_value = "1")]
+    pub departures_delay_probability: f32,
+    /// Minimum dwell in seconds of stops whose departure ended up before their arrival.
+    ///
+    /// After modifying a train, departures preceding the arrival at their stop are moved to this many seconds after the arrival, with a warning, as such times might be rejected by ZuSi.
+    #[arg(long, default_value = "0")]
+    pub min_dwell: u32,
+
+    /// Round delayed arrivals and departures to the nearest multiple of this many seconds, like 30 or 60. 0 means no rounding.
+    ///
+    /// Departures are never rounded to before their arrival.
+    #[arg(long, default_value = "0")]
+    pub round_to: u32,
+
+    /// Also modify the `.timetable.xml` file belonging to each `.trn` file.
+    ///
+    /// The timetable of `X.trn` is `X.timetable.xml` in the same folder. It receives the same entry and departure delays as the train, so the two stay consistent. Trains without a timetable file are modified as usual.
+    #[arg(long, action)]
+    pub include_timetable: bool,
+
+    /// Departures delay factors of stations (`Betrst`), overriding the departures delay factor. The keys must be trimmed.
+    #[arg(skip)]
+    pub station_factors: HashMap<String, f32>,
+    /// Acceleration multipliers of train types (`Gattung`), applied on top of the other multipliers. The keys must be trimmed.
+    #[arg(skip)]
+    pub type_multipliers: HashMap<String, f32>,
+    /// Leave the files as they are, keeping the modified trains in their reports instead, see `FileReport::modified`. Cancelled trains are not moved either.
+    #[arg(skip)]
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+impl Default for ModifyParams {
+    fn default() -> Self {
+        #[derive(Parser)]
+        struct Defaults {
+            #[command(flatten)]
+            params: ModifyParams,
+        }
+
+        Defaults::parse_from(["zusischewe"]).params
+    }
+}
+
+/// A distribution of entry delays, configured by the arguments of `ModifyParams` named after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelayType {
+    /// Exponential delay of some trains, see `ModifyParams::delay_probability`.
+    A,
+    /// Normally distributed delay, see `ModifyParams::bell_mean`.
+    B,
+    /// Gamma distributed delay, see `ModifyParams::gamma_shape`.
+    C,
+}
+
+impl DelayType {
+    pub const ALL: [DelayType; 3] = [DelayType::A, DelayType::B, DelayType::C];
+
+    /// A random entry delay in minutes. 0 if the delay type is not configured in `params`.
+    fn minutes(self, params: &ModifyParams, rng: &mut impl Rng) -> Result<f32> {
+        match self {
+            DelayType::A => {
+                let Some(p) = params.delay_probability else {
+                    return Ok(0.0);
+                };
+
+                let val: f32 = rng.gen();
+
+                if val >= p {
+                    return Ok(0.0);
+                }
+
+                Ok(params.delay_amplitude * ((params.delay_lambda * rng.gen::<f32>()).exp() - 1.0))
+            }
+            DelayType::B => {
+                let Some(bell_mean) = params.bell_mean else {
+                    return Ok(0.0);
+                };
+
+                if params.delay_bias != 0.0 {
+                    return Ok(rand_distr::SkewNormal::new(bell_mean, params.bell_deviation, params.delay_bias)
+                        .map_err(|err| ModifyError::InvalidArgument(err.to_string()))
+                        .context("unable to generate skew normal distribution for random number sampling with given parameters")?
+                        .sample(rng));
+                }
+
+                Ok(rand_distr::Normal::new(bell_mean, params.bell_deviation)
+                    .map_err(|err| ModifyError::InvalidArgument(err.to_string()))
+                    .context("unable to generate normal distribution for random number sampling with given parameters")?
+                    .sample(rng))
+            }
+            DelayType::C => {
+                let (Some(shape), Some(scale)) = (params.gamma_shape, params.gamma_scale) else {
+                    return Ok(0.0);
+                };
+
+                if !(shape > 0.0 && scale > 0.0) {
+                    return Err(ModifyError::InvalidArgument(format!("delay type C: gamma shape and scale must be positive, got shape {shape} and scale {scale}")));
+                }
+
+                Ok(rand_distr::Gamma::new(shape, scale)
+                    .map_err(|err| ModifyError::InvalidArgument(err.to_string()))
+                    .context("unable to generate gamma distribution for random number sampling with given parameters")?
+                    .sample(rng))
+            }
+        }
+    }
+}
+
+/// Unit of the entry delay arguments of `ModifyParams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelayUnit {
+    Seconds,
+    Minutes,
+}
+
+impl DelayUnit {
+    fn minutes(self, value: f32) -> f32 {
+        match self {
+            DelayUnit::Seconds => value / 60.0,
+            DelayUnit::Minutes => value,
+        }
+    }
+}
+
+/// Random entry delay in minutes of a train entering at `entry`, as applied by `modify_file`: the sum of the enabled delay types, scaled by the weather intensity, multiplied during peak windows, reduced by the advance and limited as given by `params`. Peak windows and ramps are ignored without `entry`.
+pub fn entry_delay_minutes(
+    params: &ModifyParams,
+    entry: Option<chrono::NaiveDateTime>,
+    rng: &mut impl Rng,
+) -> Result<f32> {
+    let mut minutes: f32 = 0.0;
+
+    for delay_type in DelayType::ALL {
+        if params.delay_types.is_empty() || params.delay_types.contains(&delay_type) {
+            minutes += delay_type.minutes(params, rng)?;
+        }
+    }
+
+    minutes *= weather_intensity(params, entry.map(|entry| entry.time()))?;
+
+    if entry.is_some_and(|entry| {
+        params
+            .peak_window
+            .iter()
+            .any(|window| window.contains(entry.time()))
+    }) {
+        minutes *= params.peak_multiplier;
+    }
+
+    if let Some(advance) = params.advance {
+        if rng.gen::<f32>() < params.advance_probability {
+            minutes -= advance;
+        }
+    }
+
+    if params.deny_early {
+        minutes = minutes.max(0.0);
+    }
+
+    if let Some(min) = params.min_entry_delay {
+        minutes = minutes.max(min);
+    }
+
+    if let Some(max) = params.max_entry_delay {
+        minutes = minutes.min(max);
+    }
+
+    // Every step above scales with the unit, so converting the result is the same as converting each argument.
+    Ok(params.delay_unit.minutes(minutes))
+}
+
+/// Whether a path component names a locomotive folder, like `Elektroloks`, `Dieselloks` or `Lokomotiven`.
+fn is_locomotive_folder(component: &str) -> bool {
+    let component = component.to_lowercase();
+
+    component.ends_with("lok") || component.ends_with("loks") || component.starts_with("lokomotive")
+}
+
+/// `loc_pattern` replaces the built-in detection if given, `loc_substrings` extend it, see `ModifyParams::locomotive_substring`.
+fn is_wagon_locomotive(
+    data_tag: &Element,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+) -> Result<bool> {
+    let wagon_location = data_tag
+        .attributes
+        .get("Dateiname")
+        .ok_or(ModifyError::MissingVehicleFileName)?;
+
+    if let Some(loc_pattern) = loc_pattern {
+        return Ok(loc_pattern.is_match(wagon_location));
+    }
+
+    if wagon_location.split(['\\', '/']).any(is_locomotive_folder) {
+        return Ok(true);
+    }
+
+    let lowercase = wagon_location.to_lowercase();
+
+    if loc_substrings
+        .iter()
+        .any(|substring| lowercase.contains(&substring.to_lowercase()))
+    {
+        return Ok(true);
+    }
+
+    if lowercase.contains("lok") {
+        return Err(ModifyError::NoLocomotiveInfo(wagon_location.clone()));
+    }
+
+    Ok(false)
+}
+
+/// Tags ZuSi may add in newer versions are added to `unknown_tags` and treated as not being locomotives. They are an error instead if `unknown_tags` is `None`.
+pub fn consist_has_locomotive(
+    consist: &Element,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+    mut unknown_tags: Option<&mut Vec<String>>,
+) -> Result<bool> {
+    for child in &consist.children {
+        let XMLNode::Element(element) = child else {
+            continue;
+        };
+
+        match element.name.as_str() {
+            "Datei" => {
+                if is_wagon_locomotive(element, loc_pattern, loc_substrings)? {
+                    return Ok(true);
+                }
+            }
+            "FahrzeugInfo" => {
+                let data = element
+                    .get_child("Datei")
+                    .ok_or(ModifyError::MissingVehicleFile)?;
+
+                if is_wagon_locomotive(data, loc_pattern, loc_substrings)? {
+                    return Ok(true);
+                }
+            }
+            "FahrzeugVarianten" => {
+                if consist_has_locomotive(
+                    element,
+                    loc_pattern,
+                    loc_substrings,
+                    unknown_tags.as_deref_mut(),
+                )? {
+                    return Ok(true);
+                }
+            }
+            name => match unknown_tags.as_deref_mut() {
+                Some(unknown_tags) => unknown_tags.push(name.to_owned()),
+                None => return Err(ModifyError::UnknownConsistTag(name.to_owned())),
+            },
+        }
+    }
+
+    Ok(false)
+}
+
+/// Numbers of locomotives and of all vehicles of `consist`, also inside nested variants. Unknown tags are handled like by `consist_has_locomotive`, but classifying every vehicle.
+pub fn consist_locomotives(
+    consist: &Element,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+    mut unknown_tags: Option<&mut Vec<String>>,
+) -> Result<(usize, usize)> {
+    let (mut locomotives, mut vehicles) = (0, 0);
+
+    for child in &consist.children {
+        let XMLNode::Element(element) = child else {
+            continue;
+        };
+
+        let data = match element.name.as_str() {
+            "Datei" => element,
+            "FahrzeugInfo" => element
+                .get_child("Datei")
+                .ok_or(ModifyError::MissingVehicleFile)?,
+            "FahrzeugVarianten" => {
+                let (inner_locomotives, inner_vehicles) = consist_locomotives(
+                    element,
+                    loc_pattern,
+                    loc_substrings,
+                    unknown_tags.as_deref_mut(),
+                )?;
+                locomotives += inner_locomotives;
+                vehicles += inner_vehicles;
+                continue;
+            }
+            name => {
+                match unknown_tags.as_deref_mut() {
+                    Some(unknown_tags) => unknown_tags.push(name.to_owned()),
+                    None => return Err(ModifyError::UnknownConsistTag(name.to_owned())),
+                }
+                continue;
+            }
+        };
+
+        locomotives += is_wagon_locomotive(data, loc_pattern, loc_substrings)? as usize;
+        vehicles += 1;
+    }
+
+    Ok((locomotives, vehicles))
+}
+
+/// Whether `consist` contains any vehicle (`Datei`), also inside nested variants.
+fn consist_has_vehicles(consist: &Element) -> bool {
+    consist.children.iter().any(|child| match child {
+        XMLNode::Element(element) => match element.name.as_str() {
+            "Datei" | "FahrzeugInfo" => true,
+            "FahrzeugVarianten" => consist_has_vehicles(element),
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
+/// A time of day window like `07:00-09:00`. Windows that end before they start wrap around midnight.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeWindow {
+    start: chrono::NaiveTime,
+    end: chrono::NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        match self.start <= self.end {
+            true => self.start <= time && time < self.end,
+            false => self.start <= time || time < self.end,
+        }
+    }
+}
+
+impl std::fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl Serialize for TimeWindow {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = ModifyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').ok_or_else(|| {
+            ModifyError::InvalidArgument("expected a window like `07:00-09:00`".to_owned())
+        })?;
+
+        Ok(TimeWindow {
+            start: parse_clock_time("start time", start)?,
+            end: parse_clock_time("end time", end)?,
+        })
+    }
+}
+
+fn serialize_pattern<S: serde::Serializer>(
+    pattern: &Option<Regex>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    pattern.as_ref().map(Regex::as_str).serialize(serializer)
+}
+
+/// Parses a date and time like `2016-05-20 14:00`.
+pub fn parse_date_time(time: &str) -> Result<chrono::NaiveDateTime> {
+    chrono::NaiveDateTime::parse_from_str(time.trim(), MINUTE_TIME_FORMAT).map_err(|source| {
+        ModifyError::ParseTime {
+            what: "time",
+            time: time.to_owned(),
+            source,
+        }
+    })
+}
+
+/// Parses a time of day like `14:00`.
+pub fn parse_time_of_day(time: &str) -> Result<chrono::NaiveTime> {
+    parse_clock_time("time", time)
+}
+
+/// Parses a time of day like `parse_time_of_day`, naming it `what` when failing, like `start time`.
+fn parse_clock_time(what: &'static str, time: &str) -> Result<chrono::NaiveTime> {
+    chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|source| {
+        ModifyError::ParseTime {
+            what,
+            time: time.to_owned(),
+            source,
+        }
+    })
+}
+
+/// How bad the weather is at `time`, from 0 for normal weather to 1 for full effect of the arguments, according to the ramp of `params`. 1 without ramp or `time`, which still checks the ramp.
+pub fn weather_intensity(params: &ModifyParams, time: Option<chrono::NaiveTime>) -> Result<f32> {
+    let (Some(start), Some(peak)) = (params.ramp_start, params.ramp_peak) else {
+        return Ok(1.0);
+    };
+
+    if !(start < peak && params.ramp_end.is_none_or(|end| peak < end)) {
+        return Err(ModifyError::InvalidArgument(
+            "ramp start, peak and end must follow each other on the same day".to_owned(),
+        ));
+    }
+
+    let Some(time) = time else {
+        return Ok(1.0);
+    };
+
+    // Fraction of the way from `from` to `to`.
+    let fraction = |from: chrono::NaiveTime, to: chrono::NaiveTime| {
+        (time - from).num_seconds() as f32 / (to - from).num_seconds() as f32
+    };
+
+    Ok(match params.ramp_end {
+        _ if time < start => 0.0,
+        _ if time < peak => fraction(start, peak),
+        Some(end) if time < end => 1.0 - fraction(peak, end),
+        Some(_) => 0.0,
+        None => 1.0,
+    })
+}
+
+/// Fails if the file contains more than one `Zug`, as only the first one would be modified.
+///
+/// ZuSi writes one `.trn` file per train and names it after that train, and the timetable (`.fpn`) and the `.timetable.xml` refer to the trains by file. A file with more than one `Zug` was therefore not written by ZuSi, and it is unclear whether ZuSi reads more than the first. Failing points to such a file instead of modifying part of it.
+fn check_single_train(tree: &Element) -> Result<()> {
+    let count = tree
+        .children
+        .iter()
+        .filter(|child| matches!(child, XMLNode::Element(element) if element.name == "Zug"))
+        .count();
+
+    if count > 1 {
+        return Err(ModifyError::MultipleTags {
+            tag: "Zug",
+            inside: "Zusi",
+            count,
+        });
+    }
+
+    Ok(())
+}
+
+/// Arrival time of the first `FahrplanEintrag`, which is when the train enters, or its departure time for trains starting there. `None` if it has neither.
+fn entry_time(tree: &Element) -> Result<Option<chrono::NaiveDateTime>> {
+    let zug = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let Some(time) = zug.get_child("FahrplanEintrag").and_then(|entry| {
+        entry
+            .attributes
+            .get("Ank")
+            .or_else(|| entry.attributes.get("Abf"))
+    }) else {
+        return Ok(None);
+    };
+
+    let time = parse_time("entry time", time)?;
+
+    Ok(Some(time))
+}
+
+/// Time from the first to the last `Ank` or `Abf` of the train. `None` if it has no times.
+fn journey_time(tree: &Element) -> Result<Option<chrono::TimeDelta>> {
+    let mut times = Vec::new();
+
+    for [_, ankunft, abfahrt] in stop_times(tree) {
+        for time in ankunft.iter().chain(&abfahrt) {
+            times.push(parse_time("time", time)?);
+        }
+    }
+
+    Ok(times
+        .iter()
+        .min()
+        .zip(times.iter().max())
+        .map(|(first, last)| *last - *first))
+}
+
+/// Whether the train starts at its first `FahrplanEintrag`, which then only has a departure time.
+fn is_origin_departure(tree: &Element) -> bool {
+    tree.get_child("Zug")
+        .and_then(|zug| zug.get_child("FahrplanEintrag"))
+        .is_some_and(|entry| !entry.attributes.contains_key("Ank"))
+}
+
+/// `train` is XML tag `Zug`.
+fn is_passenger(train: &Element) -> bool {
+    let zugtyp = train.attributes.get("Zugtyp");
+
+    let Some(train_type) = zugtyp else {
+        return false;
+    };
+
+    *train_type == "1"
+}
+
+/// How much more the acceleration of fast trains is reduced, see `ModifyParams::speed_penalty`.
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedPenalty {
+    /// In km/h.
+    pub threshold: f32,
+    pub penalty: f32,
+}
+
+/// Whether the consist of the train has a locomotive, see `consist_has_locomotive`. `None` if the train has no consist (`FahrzeugVarianten`), like light engines and placeholder trains may.
+pub fn train_has_locomotive(
+    tree: &Element,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+    unknown_tags: Option<&mut Vec<String>>,
+) -> Result<Option<bool>> {
+    let Some(consist) = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingConsistTag("Zug"))?
+        .get_child("FahrzeugVarianten")
+    else {
+        return Ok(None);
+    };
+
+    consist_has_locomotive(consist, loc_pattern, loc_substrings, unknown_tags)
+        .context("trying to determine whether consist has a locomotive")
+        .map(Some)
+}
+
+/// Numbers of locomotives and of all vehicles of the train of `tree`, see `consist_locomotives`.
+pub fn train_locomotives(
+    tree: &Element,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+    unknown_tags: Option<&mut Vec<String>>,
+) -> Result<(usize, usize)> {
+    let consist = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingConsistTag("Zug"))?
+        .get_child("FahrzeugVarianten")
+        .ok_or(ModifyError::MissingConsistTag("FahrzeugVarianten"))?;
+
+    consist_locomotives(consist, loc_pattern, loc_substrings, unknown_tags)
+        .context("counting locomotives of consist")
+}
+
+/// Multiplies the acceleration by `loc_multiplier` if the train `has_locomotive` and by `mu_multiplier` otherwise. `type_multipliers` maps the `Gattung` of trains to a further multiplier, trains of other types use 1.0. The new acceleration is at least `min_acceleration`, but never more than the original.
+///
+/// The original acceleration is kept in the `APBeschlOrig` attribute, which later modifications multiply instead of `APBeschl`. Resetting removes it along with the other modifications.
+///
+/// Returns the multiplier that was applied.
+pub fn modify_multiplier(
+    tree: &mut Element,
+    has_locomotive: bool,
+    loc_multiplier: f32,
+    mu_multiplier: f32,
+    type_multipliers: &HashMap<String, f32>,
+    speed_penalty: Option<SpeedPenalty>,
+    min_acceleration: Option<f32>,
+) -> Result<f32> {
+    let train = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingConsistTag("Zug"))?;
+
+    let type_multiplier = train
+        .attributes
+        .get("Gattung")
+        .and_then(|gattung| type_multipliers.get(gattung.trim()))
+        .copied()
+        .unwrap_or(1.0);
+
+    let mut multiplier = match has_locomotive {
+        true => loc_multiplier,
+        false => mu_multiplier,
+    };
+
+    if let Some(SpeedPenalty { threshold, penalty }) = speed_penalty {
+        let top_speed = match train.attributes.get("spZugNiedriger") {
+            Some(speed) => Some(parse_decimal("spZugNiedriger", speed)?),
+            None => None,
+        };
+
+        // `spZugNiedriger` is in m/s.
+        if top_speed.is_some_and(|speed| speed * 3.6 > threshold) {
+            multiplier = (1.0 - (1.0 - multiplier) * penalty).max(0.0);
+        }
+    }
+
+    multiplier *= type_multiplier;
+
+    // Files modified before keep their original acceleration, so modifying again does not compound the multipliers.
+    let (attribute, original) = match train.attributes.get(ORIGINAL_ACCELERATION) {
+        Some(original) => (ORIGINAL_ACCELERATION, original.clone()),
+        None => (
+            "APBeschl",
+            train
+                .attributes
+                .get("APBeschl")
+                .ok_or(ModifyError::MissingAcceleration)?
+                .clone(),
+        ),
+    };
+
+    let acceleration = parse_decimal(attribute, &original)?;
+
+    let mut new_acceleration = multiplier * acceleration;
+
+    if let Some(min_acceleration) = min_acceleration {
+        new_acceleration = new_acceleration.max(min_acceleration.min(acceleration));
+    }
+
+    // Files written with a German locale use decimal commas, which are kept.
+    let new_acceleration = match original.contains(',') {
+        true => new_acceleration.to_string().replace('.', ","),
+        false => new_acceleration.to_string(),
+    };
+
+    train
+        .attributes
+        .insert("APBeschl".to_owned(), new_acceleration);
+    train
+        .attributes
+        .entry(ORIGINAL_ACCELERATION.to_owned())
+        .or_insert(original);
+
+    Ok(multiplier)
+}
+
+/// Parses the number `value` of `attribute`, which may have a decimal comma instead of a point.
+fn parse_decimal(attribute: &'static str, value: &str) -> Result<f32> {
+    value
+        .replace(',', ".")
+        .parse()
+        .map_err(|source| ModifyError::ParseNumber {
+            attribute,
+            value: value.to_owned(),
+            source,
+        })
+}
+
+/// Parses a time in `TIME_FORMAT`, or in `MINUTE_TIME_FORMAT` with 0 seconds. `what` is the kind of time, like `arrival time`, see `ModifyError::ParseTime`.
+fn parse_time(what: &'static str, time: &str) -> Result<chrono::NaiveDateTime> {
+    chrono::NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .or_else(|err| {
+            chrono::NaiveDateTime::parse_from_str(time, MINUTE_TIME_FORMAT).map_err(|_| err)
+        })
+        .map_err(|source| ModifyError::ParseTime {
+            what,
+            time: time.to_owned(),
+            source,
+        })
+}
+
+/// Formats `time` like `original`, the time it replaces: without seconds if `original` has none and `time` is on a full minute, so files keep their precision.
+fn format_time(time: chrono::NaiveDateTime, original: &str) -> String {
+    let minutes_only = chrono::NaiveDateTime::parse_from_str(original, MINUTE_TIME_FORMAT).is_ok();
+
+    match minutes_only && chrono::Timelike::second(&time) == 0 {
+        true => time.format(MINUTE_TIME_FORMAT).to_string(),
+        false => time.format(TIME_FORMAT).to_string(),
+    }
+}
+
+/// Rounds `time` to the nearest multiple of `round_to` seconds. 0 means no rounding.
+fn round_time(time: chrono::NaiveDateTime, round_to: u32) -> chrono::NaiveDateTime {
+    if round_to == 0 {
+        return time;
+    }
+
+    let round_to = round_to as i64;
+    let timestamp = time.and_utc().timestamp();
+    let rounded = (timestamp + round_to / 2).div_euclid(round_to) * round_to;
+
+    time + chrono::TimeDelta::seconds(rounded - timestamp)
+}
+
+/// Delays `time` by `seconds`, rounded to `round_to` seconds. Negative `seconds` make it earlier.
+fn delayed_time(time: &str, seconds: i64, round_to: u32) -> Result<String> {
+    let parsed: chrono::NaiveDateTime = parse_time("time", time)?;
+    let delayed = parsed
+        .checked_add_signed(chrono::TimeDelta::seconds(seconds))
+        .ok_or(ModifyError::TimeOutOfRange("time"))?;
+
+    Ok(format_time(round_time(delayed, round_to), time))
+}
+
+/// Delays the departure by the dwell times `factor`, unless it is 1, plus `added_dwell` seconds, at most by `max_wait_time`.
+///
+/// The new departure time is rounded to `round_to` seconds, but never to before the arrival. Returns it and the seconds the departure was delayed by.
+fn delayed_departure(
+    ankunft: &str,
+    abfahrt: &str,
+    factor: f32,
+    added_dwell: u32,
+    max_wait_time: chrono::TimeDelta,
+    round_to: u32,
+) -> Result<(String, i64)> {
+    let arrival: chrono::NaiveDateTime = parse_time("arrival time", ankunft)?;
+
+    let departure: chrono::NaiveDateTime = parse_time("departure time", abfahrt)?;
+
+    let original_wait_time = departure - arrival;
+    let multiplied_wait_time = match factor == 1.0 {
+        true => 0,
+        false => (original_wait_time.num_seconds() as f32 * factor) as i64,
+    };
+    let delayed_wait_time =
+        chrono::TimeDelta::seconds(multiplied_wait_time + added_dwell as i64).min(max_wait_time);
+
+    let delayed_departure = departure
+        .checked_add_signed(delayed_wait_time)
+        .ok_or(ModifyError::TimeOutOfRange("arrival time"))?;
+
+    let mut rounded = round_time(delayed_departure, round_to);
+
+    if rounded < arrival {
+        rounded += chrono::TimeDelta::seconds(round_to as i64);
+    }
+
+    Ok((
+        format_time(rounded, abfahrt),
+        (rounded - departure).num_seconds(),
+    ))
+}
+
+/// Delays the arrival of the first `FahrplanEintrag`, or its departure for trains starting there. The new time is rounded to `round_to` seconds, 0 meaning no rounding. Negative `seconds` let the train enter early.
+pub fn delay_entry(tree: &mut Element, seconds: i64, round_to: u32) -> Result<()> {
+    for child in &mut tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?
+        .children
+    {
+        if let XMLNode::Element(e) = child {
+            if e.name == "FahrplanEintrag" {
+                if let Some(ankunft) = e.attributes.get_mut("Ank") {
+                    *ankunft = delayed_time(ankunft, seconds, round_to)?;
+                } else {
+                    let abfahrt = e
+                        .attributes
+                        .get_mut("Abf")
+                        .ok_or(ModifyError::MissingEntryTime)?;
+
+                    *abfahrt = delayed_time(abfahrt, seconds, round_to)?;
+                }
+
+                return Ok(());
+            }
+        }
+    }
+
+    Err(ModifyError::MissingEntry)
+}
+
+/// Delays `time` by `seconds`, but not to before `previous`, which is then set to the new time.
+fn shift_time(
+    time: &mut String,
+    seconds: i64,
+    previous: &mut Option<chrono::NaiveDateTime>,
+) -> Result<()> {
+    let parsed: chrono::NaiveDateTime = parse_time("time", time)?;
+    let mut shifted = parsed
+        .checked_add_signed(chrono::TimeDelta::seconds(seconds))
+        .ok_or(ModifyError::TimeOutOfRange("time"))?;
+
+    if let Some(previous) = previous {
+        shifted = shifted.max(*previous);
+    }
+
+    *previous = Some(shifted);
+    *time = format_time(shifted, time);
+
+    Ok(())
+}
+
+/// `offset` seconds of delay, or of advance if negative, moved `recovery` seconds closer to 0, but not past it.
+fn recovered(offset: i64, recovery: u32) -> i64 {
+    let recovery = recovery as i64;
+
+    match offset > 0 {
+        true => (offset - recovery).max(0),
+        false => (offset + recovery).min(0),
+    }
+}
+
+/// Delays all stops after the entry by `seconds`, recovering `recovery` seconds per stop, or makes them earlier if negative. Expects the entry to already be delayed by `delay_entry`.
+///
+/// Times never move before the time preceding them, so no departure precedes its arrival.
+pub fn propagate_entry_delay(tree: &mut Element, seconds: i64, recovery: u32) -> Result<()> {
+    let zug = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let mut offset = seconds;
+    let mut previous = None;
+    let mut entry = true;
+
+    for child in &mut zug.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FahrplanEintrag" {
+            continue;
+        }
+
+        if !entry {
+            offset = recovered(offset, recovery);
+        }
+
+        let has_ankunft = e.attributes.contains_key("Ank");
+
+        if let Some(ankunft) = e.attributes.get_mut("Ank") {
+            let seconds = if entry { 0 } else { offset };
+            shift_time(ankunft, seconds, &mut previous)?;
+        }
+
+        if let Some(abfahrt) = e.attributes.get_mut("Abf") {
+            // The departure is the entry of trains starting here.
+            let seconds = if entry && !has_ankunft { 0 } else { offset };
+            shift_time(abfahrt, seconds, &mut previous)?;
+        }
+
+        entry = false;
+    }
+
+    Ok(())
+}
+
+/// `station_factors` overrides `factor` for the stations (`Betrst`) it contains, and departures at `skipped_stations` are not delayed. Their keys must be trimmed. `added_dwell` seconds are added to the delay of each departure. With `skip_terminus`, the departure of the last `FahrplanEintrag` is not delayed, as passengers only alight there.
+///
+/// New departures are rounded to `round_to` seconds, see `delay_entry`. Returns the number of delayed departures and the total seconds they were delayed by.
+#[allow(clippy::too_many_arguments)]
+pub fn delay_departures(
+    tree: &mut Element,
+    factor: f32,
+    added_dwell: u32,
+    station_factors: &HashMap<String, f32>,
+    skipped_stations: &HashSet<String>,
+    skip_terminus: bool,
+    max_wait_time: chrono::TimeDelta,
+    round_to: u32,
+) -> Result<(usize, i64)> {
+    let zug = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let mut delayed = 0;
+    let mut delayed_seconds = 0;
+
+    let terminus = zug
+        .children
+        .iter()
+        .rposition(|child| matches!(child, XMLNode::Element(e) if e.name == "FahrplanEintrag"));
+
+    for (index, child) in zug.children.iter_mut().enumerate() {
+        if let XMLNode::Element(e) = child {
+            if e.name == "FahrplanEintrag" {
+                if skip_terminus && Some(index) == terminus {
+                    continue;
+                }
+
+                let betriebstelle = e.attributes.get("Betrst").map(|name| name.trim());
+
+                if betriebstelle.is_some_and(|name| skipped_stations.contains(name)) {
+                    continue;
+                }
+
+                let factor = betriebstelle
+                    .and_then(|name| station_factors.get(name))
+                    .copied()
+                    .unwrap_or(factor);
+
+                if factor == 1.0 && added_dwell == 0 {
+                    continue;
+                }
+
+                let Some(ankunft) = e.attributes.get("Ank") else {
+                    continue;
+                };
+                let ankunft = ankunft.clone();
+
+                let Some(abfahrt) = e.attributes.get_mut("Abf") else {
+                    continue;
+                };
+
+                let (delayed_abfahrt, seconds) = delayed_departure(
+                    &ankunft,
+                    abfahrt,
+                    factor,
+                    added_dwell,
+                    max_wait_time,
+                    round_to,
+                )?;
+
+                *abfahrt = delayed_abfahrt;
+                delayed += 1;
+                delayed_seconds += seconds;
+            }
+        }
+    }
+
+    Ok((delayed, delayed_seconds))
+}
+
+/// Moves the departure of each `FahrplanEintrag` with an arrival by the seconds `jitter` has for its station (`Betrst`), but not before its arrival. The keys must be trimmed. New departures are rounded to `round_to` seconds, see `delay_entry`.
+///
+/// Returns the total seconds the departures were moved by.
+pub fn jitter_departures(
+    tree: &mut Element,
+    jitter: &HashMap<String, i64>,
+    round_to: u32,
+) -> Result<i64> {
+    let zug = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let mut moved_seconds = 0;
+
+    for child in &mut zug.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FahrplanEintrag" {
+            continue;
+        }
+
+        let Some(&seconds) = e
+            .attributes
+            .get("Betrst")
+            .and_then(|name| jitter.get(name.trim()))
+        else {
+            continue;
+        };
+
+        let Some(ankunft) = e.attributes.get("Ank").cloned() else {
+            continue;
+        };
+
+        let Some(abfahrt) = e.attributes.get_mut("Abf") else {
+            continue;
+        };
+
+        moved_seconds += jittered_departure(&ankunft, abfahrt, seconds, round_to)?;
+    }
+
+    Ok(moved_seconds)
+}
+
+/// Moves `abfahrt` by `seconds`, rounded to `round_to` seconds, but not before `ankunft`. Returns the seconds it was moved by.
+fn jittered_departure(
+    ankunft: &str,
+    abfahrt: &mut String,
+    seconds: i64,
+    round_to: u32,
+) -> Result<i64> {
+    let arrival = parse_time("arrival time", ankunft)?;
+    let departure = parse_time("departure time", abfahrt)?;
+
+    let jittered =
+        parse_time("departure time", &delayed_time(abfahrt, seconds, round_to)?)?.max(arrival);
+
+    *abfahrt = format_time(jittered, abfahrt);
+
+    Ok((jittered - departure).num_seconds())
+}
+
+/// `tree` is the root of a `.timetable.xml` file. Moves departures, like `jitter_departures` does for the train, looking up stations by the `FplNameText` of `FplName`.
+///
+/// Returns whether any departure changed.
+fn jitter_timetable_departures(
+    tree: &mut Element,
+    jitter: &HashMap<String, i64>,
+    round_to: u32,
+) -> Result<bool> {
+    let timetable = tree
+        .get_mut_child("Buchfahrplan")
+        .ok_or(ModifyError::MissingTag("Buchfahrplan"))?;
+
+    let mut changed = false;
+
+    for child in &mut timetable.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FplZeile" {
+            continue;
+        }
+
+        let Some(&seconds) = e
+            .get_child("FplName")
+            .and_then(|fpl_name| fpl_name.attributes.get("FplNameText"))
+            .and_then(|name| jitter.get(name.trim()))
+        else {
+            continue;
+        };
+
+        let Some(ankunft) = e
+            .get_child("FplAnk")
+            .and_then(|fpl_ank| fpl_ank.attributes.get("Ank"))
+            .cloned()
+        else {
+            continue;
+        };
+
+        let Some(abfahrt) = e
+            .get_mut_child("FplAbf")
+            .and_then(|fpl_abf| fpl_abf.attributes.get_mut("Abf"))
+        else {
+            continue;
+        };
+
+        let original = abfahrt.clone();
+        jittered_departure(&ankunft, abfahrt, seconds, round_to)?;
+        changed |= *abfahrt != original;
+    }
+
+    Ok(changed)
+}
+
+/// A departure moved by `fix_dwell_times`.
+#[derive(Debug)]
+pub struct DwellCorrection {
+    pub station: Option<String>,
+    pub arrival: String,
+    pub departure: String,
+    pub corrected_departure: String,
+}
+
+/// Moves departures preceding the arrival at their stop to `min_dwell` seconds after the arrival. Returns the moved departures.
+pub fn fix_dwell_times(tree: &mut Element, min_dwell: u32) -> Result<Vec<DwellCorrection>> {
+    let zug = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let mut corrections = Vec::new();
+
+    for child in &mut zug.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FahrplanEintrag" {
+            continue;
+        }
+
+        let (Some(ankunft), Some(abfahrt)) = (e.attributes.get("Ank"), e.attributes.get("Abf"))
+        else {
+            continue;
+        };
+
+        let arrival = parse_time("arrival time", ankunft)?;
+        let departure = parse_time("departure time", abfahrt)?;
+
+        if departure >= arrival {
+            continue;
+        }
+
+        let corrected = format_time(
+            arrival
+                .checked_add_signed(chrono::TimeDelta::seconds(min_dwell as i64))
+                .ok_or(ModifyError::TimeOutOfRange("departure time"))?,
+            abfahrt,
+        );
+
+        corrections.push(DwellCorrection {
+            station: e
+                .attributes
+                .get("Betrst")
+                .map(|station| station.trim().to_owned()),
+            arrival: ankunft.clone(),
+            departure: abfahrt.clone(),
+            corrected_departure: corrected.clone(),
+        });
+
+        e.attributes.insert("Abf".to_owned(), corrected);
+    }
+
+    Ok(corrections)
+}
+
+/// Logs a warning for each of `corrections` of the train of `path`.
+fn warn_dwell_corrections(path: &Path, corrections: &[DwellCorrection]) {
+    for correction in corrections {
+        log::warn!(
+            "{}: departure {} at {} was before arrival {}, moved to {}",
+            path.to_string_lossy(),
+            correction.departure,
+            correction.station.as_deref().unwrap_or("unnamed stop"),
+            correction.arrival,
+            correction.corrected_departure
+        );
+    }
+}
+
+/// `tree` is the root of a `.timetable.xml` file. Delays the first arrival, like `delay_entry` does for the train, or the first departure if the train is an `origin` departure.
+fn delay_timetable_entry(
+    tree: &mut Element,
+    seconds: i64,
+    origin: bool,
+    round_to: u32,
+) -> Result<()> {
+    let timetable = tree
+        .get_mut_child("Buchfahrplan")
+        .ok_or(ModifyError::MissingTag("Buchfahrplan"))?;
+
+    for child in &mut timetable.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FplZeile" {
+            continue;
+        }
+
+        if origin {
+            let Some(fpl_abf) = e.get_mut_child("FplAbf") else {
+                continue;
+            };
+
+            let abfahrt =
+                fpl_abf
+                    .attributes
+                    .get_mut("Abf")
+                    .ok_or(ModifyError::MissingAttribute {
+                        attribute: "Abf",
+                        tag: "FplAbf",
+                    })?;
+
+            *abfahrt = delayed_time(abfahrt, seconds, round_to)?;
+
+            return Ok(());
+        }
+
+        let Some(fpl_ank) = e.get_mut_child("FplAnk") else {
+            continue;
+        };
+
+        let ankunft = fpl_ank
+            .attributes
+            .get_mut("Ank")
+            .ok_or(ModifyError::MissingAttribute {
+                attribute: "Ank",
+                tag: "FplAnk",
+            })?;
+
+        *ankunft = delayed_time(ankunft, seconds, round_to)?;
+
+        return Ok(());
+    }
+
+    match origin {
+        true => Err(ModifyError::MissingTimetableEntry { tag: "FplAbf" }),
+        false => Err(ModifyError::MissingTimetableEntry { tag: "FplAnk" }),
+    }
+}
+
+/// `tree` is the root of a `.timetable.xml` file. Delays all stops after the entry, like `propagate_entry_delay` does for the train.
+fn propagate_timetable_entry_delay(
+    tree: &mut Element,
+    seconds: i64,
+    recovery: u32,
+    origin: bool,
+) -> Result<()> {
+    let timetable = tree
+        .get_mut_child("Buchfahrplan")
+        .ok_or(ModifyError::MissingTag("Buchfahrplan"))?;
+
+    let mut offset = seconds;
+    let mut previous = None;
+
+    for child in &mut timetable.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FplZeile" {
+            continue;
+        }
+
+        let has_ankunft = e.get_child("FplAnk").is_some();
+        let has_abfahrt = e.get_child("FplAbf").is_some();
+
+        let entry = previous.is_none();
+
+        // Lines before the entry and lines without times are left as they are. Trains starting at their first stop enter at its departure.
+        if !has_ankunft && ((entry && !origin) || !has_abfahrt) {
+            continue;
+        }
+
+        if !entry {
+            offset = recovered(offset, recovery);
+        }
+
+        if let Some(ankunft) = e
+            .get_mut_child("FplAnk")
+            .and_then(|fpl_ank| fpl_ank.attributes.get_mut("Ank"))
+        {
+            let seconds = if entry { 0 } else { offset };
+            shift_time(ankunft, seconds, &mut previous)?;
+        }
+
+        if let Some(abfahrt) = e
+            .get_mut_child("FplAbf")
+            .and_then(|fpl_abf| fpl_abf.attributes.get_mut("Abf"))
+        {
+            let seconds = if entry && !has_ankunft { 0 } else { offset };
+            shift_time(abfahrt, seconds, &mut previous)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `tree` is the root of a `.timetable.xml` file. Delays departures, like `delay_departures` does for the train.
+///
+/// Stations are looked up in `station_factors` and `skipped_stations` by the `FplNameText` of `FplName`. The terminus is the last `FplZeile` with a time.
+///
+/// Returns the number of delayed departures.
+#[allow(clippy::too_many_arguments)]
+fn delay_timetable_departures(
+    tree: &mut Element,
+    factor: f32,
+    added_dwell: u32,
+    station_factors: &HashMap<String, f32>,
+    skipped_stations: &HashSet<String>,
+    skip_terminus: bool,
+    max_wait_time: chrono::TimeDelta,
+    round_to: u32,
+) -> Result<usize> {
+    let timetable = tree
+        .get_mut_child("Buchfahrplan")
+        .ok_or(ModifyError::MissingTag("Buchfahrplan"))?;
+
+    let mut delayed = 0;
+
+    let terminus = timetable.children.iter().rposition(|child| {
+        matches!(child, XMLNode::Element(e) if e.name == "FplZeile"
+            && (e.get_child("FplAnk").is_some() || e.get_child("FplAbf").is_some()))
+    });
+
+    for (index, child) in timetable.children.iter_mut().enumerate() {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FplZeile" || (skip_terminus && Some(index) == terminus) {
+            continue;
+        }
+
+        let name = e
+            .get_child("FplName")
+            .and_then(|fpl_name| fpl_name.attributes.get("FplNameText"))
+            .map(|name| name.trim());
+
+        if name.is_some_and(|name| skipped_stations.contains(name)) {
+            continue;
+        }
+
+        let factor = name
+            .and_then(|name| station_factors.get(name))
+            .copied()
+            .unwrap_or(factor);
+
+        if factor == 1.0 && added_dwell == 0 {
+            continue;
+        }
+
+        let Some(ankunft) = e
+            .get_child("FplAnk")
+            .and_then(|fpl_ank| fpl_ank.attributes.get("Ank"))
+        else {
+            continue;
+        };
+        let ankunft = ankunft.clone();
+
+        let Some(abfahrt) = e
+            .get_mut_child("FplAbf")
+            .and_then(|fpl_abf| fpl_abf.attributes.get_mut("Abf"))
+        else {
+            continue;
+        };
+
+        *abfahrt = delayed_departure(
+            &ankunft,
+            abfahrt,
+            factor,
+            added_dwell,
+            max_wait_time,
+            round_to,
+        )?
+        .0;
+        delayed += 1;
+    }
+
+    Ok(delayed)
+}
+
+/// How a file is stored, so it can be written back the same way.
+#[derive(Debug, Clone)]
+pub struct FileFormat {
+    encoding: &'static Encoding,
+    bom: bool,
+    /// The `<?xml ...?>` declaration, written back as it was.
+    declaration: Option<String>,
+    /// `None` if the whole file is on a single line.
+    line_separator: Option<&'static str>,
+    final_newline: bool,
+}
+
+/// The encoding named by the `encoding` attribute of an XML declaration.
+fn declared_encoding(declaration: &str) -> Option<&'static Encoding> {
+    let (_, rest) = declaration.split_once("encoding")?;
+    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next()?;
+    let (label, _) = rest[quote.len_utf8()..].split_once(quote)?;
+
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Decodes the file according to its XML declaration. Files claiming to be (or not declaring an encoding, thus being) UTF-8 that are not valid UTF-8 are decoded as Windows-1252, the usual encoding of older ZuSi files.
+pub fn read_file(path: &Path) -> Result<(Element, FileFormat)> {
+    let bytes = fs::read(path)?;
+
+    let bom = bytes.starts_with(b"\xef\xbb\xbf");
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes);
+
+    // The declaration is ASCII in every encoding ZuSi uses.
+    let declaration = bytes
+        .starts_with(b"<?xml")
+        .then(|| bytes.windows(2).position(|window| window == b"?>"))
+        .flatten()
+        .map(|end| String::from_utf8_lossy(&bytes[..end + 2]).into_owned());
+
+    let declared = match bom {
+        true => Some(encoding_rs::UTF_8),
+        false => declaration.as_deref().and_then(declared_encoding),
+    };
+
+    let (contents, encoding) = match declared.unwrap_or(encoding_rs::UTF_8) {
+        encoding if encoding == encoding_rs::UTF_8 => match std::str::from_utf8(bytes) {
+            Ok(contents) => (contents.to_owned(), encoding_rs::UTF_8),
+            Err(_) => (
+                encoding_rs::WINDOWS_1252
+                    .decode_without_bom_handling(bytes)
+                    .0
+                    .into_owned(),
+                encoding_rs::WINDOWS_1252,
+            ),
+        },
+        encoding => (
+            encoding.decode_without_bom_handling(bytes).0.into_owned(),
+            encoding,
+        ),
+    };
+
+    // The parser would decode the contents again according to the declaration, so it only gets what follows it.
+    let body = match &declaration {
+        Some(declaration) => &contents[declaration.len()..],
+        None => &contents[..],
+    };
+
+    let format = FileFormat {
+        encoding,
+        bom,
+        declaration,
+        line_separator: match () {
+            _ if contents.contains("\r\n") => Some("\r\n"),
+            _ if contents.trim_end().contains('\n') => Some("\n"),
+            _ => None,
+        },
+        final_newline: contents.ends_with('\n'),
+    };
+
+    // ZuSi 2 timetables are text files, not XML.
+    if !body.trim_start().starts_with('<') {
+        return Err(ModifyError::UnsupportedFormat(
+            "not an XML file, only ZuSi 3 files are supported".to_owned(),
+        ));
+    }
+
+    let tree = Element::parse(body.as_bytes())?;
+
+    if tree.name != "Zusi" {
+        return Err(ModifyError::UnsupportedFormat(format!(
+            "root element is `{}` instead of `Zusi`",
+            tree.name
+        )));
+    }
+
+    Ok((tree, format))
+}
+
+/// Writes every element on its own line without indentation, like ZuSi does.
+pub fn write_file(path: &Path, tree: Element, format: &FileFormat) -> Result<()> {
+    let line_separator = format.line_separator.unwrap_or("\n");
+
+    let mut xml = Vec::new();
+
+    if let Some(declaration) = &format.declaration {
+        xml.extend(declaration.as_bytes());
+
+        if format.line_separator.is_some() {
+            xml.extend(line_separator.as_bytes());
+        }
+    }
+
+    let config = EmitterConfig::new()
+        .write_document_declaration(false)
+        .perform_indent(format.line_separator.is_some())
+        .indent_string("")
+        .line_separator(line_separator)
+        .pad_self_closing(false);
+
+    tree.write_with_config(&mut xml, config)?;
+
+    if format.final_newline {
+        xml.extend(line_separator.as_bytes());
+    }
+
+    let xml = String::from_utf8(xml)?;
+    let (encoded, _, unmappable) = format.encoding.encode(&xml);
+
+    if unmappable {
+        return Err(ModifyError::Unencodable(format.encoding.name()));
+    }
+
+    let mut file = File::create(path)?;
+
+    if format.bom {
+        file.write_all(b"\xef\xbb\xbf")?;
+    }
+
+    file.write_all(&encoded)?;
+
+    Ok(())
+}
+
+/// Times of a `FahrplanEintrag` before and after modification.
+#[derive(Debug, Clone, Serialize)]
+pub struct StopChange {
+    /// Index of the `FahrplanEintrag` in the train, starting at 0.
+    pub stop: usize,
+    pub station: Option<String>,
+    pub original_arrival: Option<String>,
+    pub arrival: Option<String>,
+    pub original_departure: Option<String>,
+    pub departure: Option<String>,
+}
+
+/// `Betrst`, `Ank` and `Abf` of all `FahrplanEintrag` of the train, in order.
+fn stop_times(tree: &Element) -> Vec<[Option<String>; 3]> {
+    let Some(zug) = tree.get_child("Zug") else {
+        return Vec::new();
+    };
+
+    zug.children
+        .iter()
+        .filter_map(|node| match node {
+            XMLNode::Element(e) if e.name == "FahrplanEintrag" => {
+                Some(["Betrst", "Ank", "Abf"].map(|name| e.attributes.get(name).cloned()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Adds the stops whose times differ between `before` and `after`, both from `stop_times`, to `changes`. Stops already in `changes` keep their original times.
+fn record_stop_changes(
+    changes: &mut Vec<StopChange>,
+    before: &[[Option<String>; 3]],
+    after: &[[Option<String>; 3]],
+) {
+    for (stop, (before, after)) in before.iter().zip(after).enumerate() {
+        if before[1] == after[1] && before[2] == after[2] {
+            continue;
+        }
+
+        match changes.iter_mut().find(|change| change.stop == stop) {
+            Some(change) => {
+                change.arrival.clone_from(&after[1]);
+                change.departure.clone_from(&after[2]);
+            }
+            None => changes.push(StopChange {
+                stop,
+                station: before[0].as_ref().map(|station| station.trim().to_owned()),
+                original_arrival: before[1].clone(),
+                arrival: after[1].clone(),
+                original_departure: before[2].clone(),
+                departure: after[2].clone(),
+            }),
+        }
+    }
+
+    changes.sort_by_key(|change| change.stop);
+}
+
+/// What `modify_file` did to a file.
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub file: PathBuf,
+    /// `Gattung` and `Nummer` of the train, or the file name if it has no `Nummer`.
+    pub train: String,
+    /// `None` if the acceleration was not modified, so the consist was not inspected.
+    pub has_locomotive: Option<bool>,
+    /// Tags of the consist that were not recognized, see `consist_has_locomotive`.
+    pub unknown_tags: Vec<String>,
+    /// Whether the train was cancelled, in which case it was moved to the `_cancelled` folder and not modified.
+    pub cancelled: bool,
+    /// Whether the train enters during a skip window or before the sim start, in which case it was not modified.
+    pub skipped: bool,
+    /// Whether something in the file was changed, so it was written unless with `ModifyParams::dry_run`. Cancelled trains are moved without changing them.
+    pub changed: bool,
+    /// Whether the acceleration was left as it is because it was unclear whether the train has a locomotive, see `ModifyParams::no_multiplier_if_no_loco_info`.
+    pub multiplier_skipped: bool,
+    /// Whether the acceleration was left as it is because the train has no consist (`FahrzeugVarianten`).
+    pub consist_missing: bool,
+    pub multiplier: f32,
+    /// Negative if the train enters early.
+    pub entry_delay_seconds: i64,
+    pub departures_delayed: usize,
+    /// Sum of the delays of all delayed departures.
+    pub departures_delay_seconds: i64,
+    /// The stops whose times were changed, in order.
+    pub stop_changes: Vec<StopChange>,
+    /// The changed train with `ModifyParams::dry_run`, as it would have been written.
+    #[serde(skip)]
+    pub modified: Option<Element>,
+}
+
+/// `Gattung` and `Nummer` of the train of `path`, or the file name if it has no `Nummer`.
+fn train_name(tree: &Element, path: &Path) -> String {
+    let zug = tree.get_child("Zug");
+    let attribute = |name| zug.and_then(|zug| zug.attributes.get(name));
+
+    match attribute("Nummer") {
+        Some(nummer) => format!(
+            "{} {nummer}",
+            attribute("Gattung").map_or("", String::as_str)
+        ),
+        None => path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+/// Random number generator of the train of `path`, seeded by `seed` and the `Gattung` and `Nummer` of the train, or the file name if it has no `Nummer`.
+///
+/// A train thus gets the same random numbers with the same seed, no matter which other trains are modified and in which order.
+pub fn train_rng(tree: &Element, path: &Path, seed: u64) -> StdRng {
+    let name = train_name(tree, path);
+
+    // FNV-1a, unlike the hasher of the standard library stable across Rust versions.
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in seed.to_le_bytes().iter().chain(name.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    StdRng::seed_from_u64(hash)
+}
+
+/// `seed` seeds the random numbers of the train, see `train_rng`.
+pub fn modify_file(path: &Path, params: &ModifyParams, seed: u64) -> Result<FileReport> {
+    let (mut tree, format) = read_file(path)?;
+    check_single_train(&tree)?;
+    let rng = &mut train_rng(&tree, path, seed);
+
+    let mut report = FileReport {
+        file: path.to_path_buf(),
+        train: train_name(&tree, path),
+        has_locomotive: None,
+        unknown_tags: Vec::new(),
+        cancelled: false,
+        skipped: false,
+        changed: false,
+        multiplier_skipped: false,
+        consist_missing: false,
+        multiplier: 1.0,
+        entry_delay_seconds: 0,
+        departures_delayed: 0,
+        departures_delay_seconds: 0,
+        stop_changes: Vec::new(),
+        modified: None,
+    };
+
+    let entry = match params.peak_window.is_empty()
+        && params.skip_window.is_empty()
+        && params.sim_start.is_none()
+        && params.ramp_start.is_none()
+    {
+        true => None,
+        false => entry_time(&tree)?,
+    };
+
+    if entry.is_some_and(|entry| {
+        params
+            .skip_window
+            .iter()
+            .any(|window| window.contains(entry.time()))
+    }) {
+        report.skipped = true;
+
+        log::debug!("{}: enters during a skip window", path.to_string_lossy());
+
+        return Ok(report);
+    }
+
+    if let (Some(entry), Some(sim_start)) = (entry, params.sim_start) {
+        if entry < sim_start {
+            report.skipped = true;
+
+            log::debug!("{}: enters before the sim start", path.to_string_lossy());
+
+            return Ok(report);
+        }
+    }
+
+    if params
+        .cancel_probability
+        .is_some_and(|probability| rng.gen::<f32>() < probability)
+    {
+        if !params.dry_run {
+            cancel_train(path).context("cancelling train")?;
+        }
+        report.cancelled = true;
+
+        log::debug!("{}: cancelled", path.to_string_lossy());
+
+        return Ok(report);
+    }
+
+    // Only write files that were actually changed, as writing reformats the whole file.
+    let mut dirty = false;
+    let original_times = stop_times(&tree);
+
+    // multiplier
+    {
+        let intensity = weather_intensity(params, entry.map(|entry| entry.time()))?;
+
+        let friction_multiplier = |needed: f32| {
+            if let Some(percent) = params.accel_reduction_percent {
+                return 1.0 - percent / 100.0;
+            }
+
+            let multiplier = 1.0 - intensity * (1.0 - (params.friction / needed).min(1.0));
+
+            multiplier * params.multiplier.unwrap_or(1.0)
+        };
+
+        let loc_multiplier = friction_multiplier(params.loc_needed);
+        let mu_multiplier = friction_multiplier(params.mu_needed);
+
+        // This is only here to not try to perform an unneeded operation if no changes are needed. If friction >= *_needed, then *_multiplier = 1.0, so this check is enough.
+        let needed =
+            loc_multiplier != 1.0 || mu_multiplier != 1.0 || !params.type_multipliers.is_empty();
+        // Light engines and placeholder trains may have no consist, they are still delayed.
+        let has_consist = tree
+            .get_child("Zug")
+            .is_some_and(|zug| zug.get_child("FahrzeugVarianten").is_some());
+
+        if needed && !has_consist {
+            report.consist_missing = true;
+
+            log::debug!(
+                "{}: no consist (`FahrzeugVarianten`), acceleration not modified",
+                path.to_string_lossy()
+            );
+        } else if needed {
+            let mut unknown_tags = Vec::new();
+
+            let (has_locomotive, loc_multiplier) = match params.ratio_model {
+                false => (
+                    train_has_locomotive(
+                        &tree,
+                        params.loc_pattern.as_ref(),
+                        &params.locomotive_substring,
+                        (!params.strict).then_some(&mut unknown_tags),
+                    )
+                    .context("applying multiplier")?
+                    // Trains without consist were handled above.
+                    .unwrap_or(false),
+                    loc_multiplier,
+                ),
+                true => {
+                    let (locomotives, vehicles) = train_locomotives(
+                        &tree,
+                        params.loc_pattern.as_ref(),
+                        &params.locomotive_substring,
+                        (!params.strict).then_some(&mut unknown_tags),
+                    )
+                    .context("applying multiplier")?;
+
+                    // Trains without a locomotive are multiple units, which are powered throughout.
+                    let powered = match locomotives {
+                        0 => 1.0,
+                        locomotives => locomotives as f32 / vehicles as f32,
+                    };
+                    let needed =
+                        params.mu_needed + (1.0 - powered) * (params.loc_needed - params.mu_needed);
+
+                    (locomotives != 0, friction_multiplier(needed))
+                }
+            };
+
+            unknown_tags.sort();
+            unknown_tags.dedup();
+
+            for tag in &unknown_tags {
+                log::debug!(
+                    "{}: unknown tag `{tag}` in consist, treated as no locomotive",
+                    path.to_string_lossy()
+                );
+            }
+
+            // Trains with a locomotive found are certain, unknown tags cannot change that.
+            let uncertain = !has_locomotive
+                && (!unknown_tags.is_empty()
+                    || !tree
+                        .get_child("Zug")
+                        .and_then(|zug| zug.get_child("FahrzeugVarianten"))
+                        .is_some_and(consist_has_vehicles));
+
+            report.has_locomotive = Some(has_locomotive);
+            report.unknown_tags = unknown_tags;
+
+            if uncertain && params.no_multiplier_if_no_loco_info {
+                report.multiplier_skipped = true;
+
+                log::debug!(
+                    "{}: unclear whether the train has a locomotive, acceleration not modified",
+                    path.to_string_lossy()
+                );
+            } else {
+                let multiplier = modify_multiplier(
+                    &mut tree,
+                    has_locomotive,
+                    loc_multiplier,
+                    mu_multiplier,
+                    &params.type_multipliers,
+                    params.speed_penalty.map(|penalty| SpeedPenalty {
+                        threshold: params.speed_threshold,
+                        penalty,
+                    }),
+                    params.min_acceleration,
+                )
+                .context("applying multiplier")?;
+
+                report.multiplier = multiplier;
+                dirty = true;
+
+                log::debug!(
+                    "{}: multiplied `APBeschl` by {multiplier}",
+                    path.to_string_lossy()
+                );
+            }
+        }
+    }
+
+    let seconds;
+    let origin = is_origin_departure(&tree);
+
+    // delay entry
+    {
+        let mut minutes = entry_delay_minutes(params, entry, rng)?;
+
+        if let Some(fraction) = params.max_delay_fraction {
+            if let Some(journey) = journey_time(&tree)? {
+                minutes = minutes.min(fraction * journey.num_seconds() as f32 / 60.0);
+            }
+        }
+
+        seconds = (minutes * 60.0) as i64;
+
+        if seconds != 0 {
+            delay_entry(&mut tree, seconds, params.round_to).context("delaying entry")?;
+            report.entry_delay_seconds = seconds;
+            dirty = true;
+
+            log::debug!("{}: delayed entry by {seconds} s", path.to_string_lossy());
+
+            if params.propagate_entry_delay {
+                propagate_entry_delay(&mut tree, seconds, params.recovery_per_stop)
+                    .context("propagating entry delay")?;
+            }
+
+            if params.entry_delay_note {
+                note_entry_delay(&mut tree, 0, seconds).context("noting entry delay")?;
+            }
+        }
+    }
+
+    let max_wait_time = chrono::TimeDelta::seconds((params.departures_max_delay * 60.0) as i64);
+
+    // Freight trains have no boarding passengers, so station factors and added dwells do not apply to them.
+    let no_station_factors = HashMap::new();
+    let (departures_delay_factor, added_dwell, station_factors) =
+        match tree.get_child("Zug").is_some_and(is_passenger) {
+            true => (
+                params.departures_delay_factor,
+                params.min_added_dwell,
+                &params.station_factors,
+            ),
+            false => (
+                params.freight_departures_delay_factor,
+                0,
+                &no_station_factors,
+            ),
+        };
+
+    let delay_departures_needed =
+        departures_delay_factor != 1.0 || added_dwell != 0 || !station_factors.is_empty();
+
+    // Drawn once per station, so the train and its timetable skip the same stops, and stations visited twice are skipped with the same probability.
+    let mut skipped_stations = HashSet::new();
+
+    if delay_departures_needed && params.departures_delay_probability < 1.0 {
+        let mut skipped = HashMap::new();
+
+        for [station, _, _] in stop_times(&tree) {
+            if let Some(station) = station {
+                skipped
+                    .entry(station.trim().to_owned())
+                    .or_insert_with(|| rng.gen::<f32>() >= params.departures_delay_probability);
+            }
+        }
+
+        skipped_stations.extend(
+            skipped
+                .into_iter()
+                .filter_map(|(station, skipped)| skipped.then_some(station)),
+        );
+    }
+
+    // Drawn per station, so the train and its timetable get the same jitter.
+    let mut jitter = HashMap::new();
+
+    if params.departure_jitter > 0 {
+        let max = params.departure_jitter as i64;
+        let min = if params.deny_early { 0 } else { -max };
+
+        for [station, _, _] in stop_times(&tree) {
+            if let Some(station) = station {
+                jitter
+                    .entry(station.trim().to_owned())
+                    .or_insert_with(|| rng.gen_range(min..=max));
+            }
+        }
+    }
+
+    // delay_departure
+    if delay_departures_needed {
+        (report.departures_delayed, report.departures_delay_seconds) = delay_departures(
+            &mut tree,
+            departures_delay_factor,
+            added_dwell,
+            station_factors,
+            &skipped_stations,
+            params.no_terminus_dwell,
+            max_wait_time,
+            params.round_to,
+        )
+        .context("delaying departures")?;
+
+        dirty |= report.departures_delayed != 0;
+
+        log::debug!(
+            "{}: delayed {} departures by {} s in total",
+            path.to_string_lossy(),
+            report.departures_delayed,
+            report.departures_delay_seconds
+        );
+    }
+
+    if !jitter.is_empty() {
+        let before = stop_times(&tree);
+        let seconds = jitter_departures(&mut tree, &jitter, params.round_to)
+            .context("jittering departures")?;
+        report.departures_delay_seconds += seconds;
+        // Jitter of 0 seconds, or departures held at their arrival, change nothing.
+        dirty |= stop_times(&tree) != before;
+
+        log::debug!(
+            "{}: jittered departures by {seconds} s in total",
+            path.to_string_lossy()
+        );
+    }
+
+    // timetable
+    if params.include_timetable {
+        let timetable_path = path.with_extension("timetable.xml");
+
+        if timetable_path.exists() {
+            let (mut timetable, timetable_format) = read_file(&timetable_path)?;
+            let mut timetable_dirty = false;
+
+            if seconds != 0 {
+                delay_timetable_entry(&mut timetable, seconds, origin, params.round_to)
+                    .context("delaying entry in timetable")?;
+                timetable_dirty = true;
+
+                if params.propagate_entry_delay {
+                    propagate_timetable_entry_delay(
+                        &mut timetable,
+                        seconds,
+                        params.recovery_per_stop,
+                        origin,
+                    )
+                    .context("propagating entry delay in timetable")?;
+                }
+            }
+
+            if delay_departures_needed {
+                let delayed = delay_timetable_departures(
+                    &mut timetable,
+                    departures_delay_factor,
+                    added_dwell,
+                    station_factors,
+                    &skipped_stations,
+                    params.no_terminus_dwell,
+                    max_wait_time,
+                    params.round_to,
+                )
+                .context("delaying departures in timetable")?;
+
+                timetable_dirty |= delayed != 0;
+            }
+
+            if !jitter.is_empty() {
+                timetable_dirty |=
+                    jitter_timetable_departures(&mut timetable, &jitter, params.round_to)
+                        .context("jittering departures in timetable")?;
+            }
+
+            if timetable_dirty && !params.dry_run {
+                write_file(&timetable_path, timetable, &timetable_format)?;
+
+                log::debug!("{}: modified timetable", path.to_string_lossy());
+            }
+        }
+    }
+
+    if dirty {
+        let corrections =
+            fix_dwell_times(&mut tree, params.min_dwell).context("fixing dwell times")?;
+        warn_dwell_corrections(path, &corrections);
+
+        record_stop_changes(
+            &mut report.stop_changes,
+            &original_times,
+            &stop_times(&tree),
+        );
+
+        match params.dry_run {
+            true => report.modified = Some(tree),
+            false => write_file(path, tree, &format)?,
+        }
+        report.changed = true;
+    }
+
+    Ok(report)
+}
+
+/// `dir` ending in the name of the folder, so folders next to it can be named after it. Returns the name too.
+///
+/// Paths not ending in a name, like `.` or `Scenario/..`, are resolved first. Fails for paths without any name, like `/`.
+pub fn named_folder(dir: &Path) -> Result<(PathBuf, OsString)> {
+    let dir = match dir.file_name() {
+        Some(_) => dir.to_path_buf(),
+        None => dir
+            .canonicalize()
+            .with_context(|| format!("resolving `{}`", dir.to_string_lossy()))?,
+    };
+
+    let name = dir
+        .file_name()
+        .ok_or_else(|| {
+            ModifyError::InvalidArgument(format!(
+                "`{}` does not end in a folder name, unable to name `_zsw` folder after it",
+                dir.to_string_lossy()
+            ))
+        })?
+        .to_os_string();
+
+    Ok((dir, name))
+}
+
+/// The `_zsw` folder next to folder `dir`, like `Scenario_zsw` for `Scenario` or `Scenario/`. See `named_folder` for paths not ending in a name.
+pub fn copy_name(dir: &Path) -> Result<PathBuf> {
+    let (dir, mut file_name) = named_folder(dir)?;
+    file_name.push("_zsw");
+
+    Ok(dir.with_file_name(file_name))
+}
+
+/// Moves the train of `path` and its timetable into the `_cancelled` folder next to it.
+fn cancel_train(path: &Path) -> Result<()> {
+    let folder = path.with_file_name(CANCELLED_FOLDER);
+
+    fs::create_dir_all(&folder).context("creating folder of cancelled trains")?;
+
+    for file in [path.to_path_buf(), path.with_extension("timetable.xml")] {
+        if let (true, Some(file_name)) = (file.exists(), file.file_name()) {
+            fs::rename(&file, folder.join(file_name))
+                .with_context(|| format!("moving `{}`", file.to_string_lossy()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Entry station (`Betrst`) and entry time of the train of `report`, as modified so far. `None` if its first `FahrplanEintrag` lacks either.
+fn entry_station(report: &FileReport) -> Result<Option<(String, chrono::NaiveDateTime)>> {
+    let read;
+    let tree = match &report.modified {
+        Some(tree) => tree,
+        None => {
+            read = read_file(&report.file)?.0;
+            &read
+        }
+    };
+
+    let Some(time) = entry_time(tree)? else {
+        return Ok(None);
+    };
+
+    let station = tree
+        .get_child("Zug")
+        .and_then(|zug| zug.get_child("FahrplanEintrag"))
+        .and_then(|entry| entry.attributes.get("Betrst"));
+
+    Ok(station.map(|station| (station.trim().to_owned(), time)))
+}
+
+/// Further delays the entry of the train of `report` by `seconds`, and of its timetable if `params` include timetables. The changed stops are added to the report.
+fn delay_file_entry(report: &mut FileReport, seconds: i64, params: &ModifyParams) -> Result<()> {
+    let path = &report.file;
+    let (tree, format) = read_file(path)?;
+    // With dry run, the changes so far are only in the report.
+    let mut tree = report.modified.take().unwrap_or(tree);
+    let origin = is_origin_departure(&tree);
+    let original_times = stop_times(&tree);
+
+    delay_entry(&mut tree, seconds, params.round_to).context("delaying entry")?;
+
+    if params.propagate_entry_delay {
+        propagate_entry_delay(&mut tree, seconds, params.recovery_per_stop)
+            .context("propagating entry delay")?;
+    }
+
+    if params.entry_delay_note {
+        let previous = report.entry_delay_seconds;
+        note_entry_delay(&mut tree, previous, previous + seconds).context("noting entry delay")?;
+    }
+
+    let corrections = fix_dwell_times(&mut tree, params.min_dwell).context("fixing dwell times")?;
+    warn_dwell_corrections(path, &corrections);
+
+    record_stop_changes(
+        &mut report.stop_changes,
+        &original_times,
+        &stop_times(&tree),
+    );
+
+    match params.dry_run {
+        true => report.modified = Some(tree),
+        false => write_file(path, tree, &format)?,
+    }
+    report.changed = true;
+
+    let timetable_path = path.with_extension("timetable.xml");
+
+    if params.include_timetable && timetable_path.exists() && !params.dry_run {
+        let (mut timetable, timetable_format) = read_file(&timetable_path)?;
+
+        delay_timetable_entry(&mut timetable, seconds, origin, params.round_to)
+            .context("delaying entry in timetable")?;
+
+        if params.propagate_entry_delay {
+            propagate_timetable_entry_delay(
+                &mut timetable,
+                seconds,
+                params.recovery_per_stop,
+                origin,
+            )
+            .context("propagating entry delay in timetable")?;
+        }
+
+        write_file(&timetable_path, timetable, &timetable_format)?;
+    }
+
+    Ok(())
+}
+
+/// Delays the trains of `reports`, which must already be modified by `modify_file`, so trains entering at the same station (`Betrst`) enter at least `headway` seconds after each other. Trains keep the order they enter in, and the entry delays of `reports` are updated.
+///
+/// Cancelled and skipped trains and trains without an entry station or time are left as they are.
+pub fn enforce_headway(
+    reports: &mut [FileReport],
+    headway: u32,
+    params: &ModifyParams,
+) -> Result<()> {
+    let mut stations = entry_stations(reports)?;
+
+    let headway = chrono::TimeDelta::seconds(headway as i64);
+
+    for entries in stations.values_mut() {
+        // Trains entering at the same time keep the order of their files.
+        entries.sort();
+
+        let mut previous: Option<chrono::NaiveDateTime> = None;
+
+        for &(mut time, index) in entries.iter() {
+            if let Some(earliest) = previous.map(|previous| previous + headway) {
+                if time < earliest {
+                    let seconds = (earliest - time).num_seconds();
+                    let report = &mut reports[index];
+
+                    delay_file_entry(report, seconds, params).with_context(|| {
+                        format!("enforcing headway, path: {}", report.file.to_string_lossy())
+                    })?;
+
+                    report.entry_delay_seconds += seconds;
+                    time = earliest;
+
+                    log::debug!(
+                        "{}: delayed entry by {seconds} s for headway",
+                        report.file.to_string_lossy()
+                    );
+                }
+            }
+
+            previous = Some(time);
+        }
+    }
+
+    Ok(())
+}
+
+/// Blends the entry delay of each train of `reports`, which must already be modified by `modify_file`, with that of the train entering before it at the same station (`Betrst`). A train gets `1 - correlation` of its own delay and `correlation` of the delay its predecessor got from blending, so late trains make the trains after them late too.
+///
+/// Trains are ordered by their entry time before the delays. Like for the headway, cancelled and skipped trains and trains without an entry station or time are left as they are. The entry delays of `reports` are updated.
+pub fn correlate_delays(
+    reports: &mut [FileReport],
+    correlation: f32,
+    params: &ModifyParams,
+) -> Result<()> {
+    let mut stations = entry_stations(reports)?;
+
+    for entries in stations.values_mut() {
+        for (time, index) in entries.iter_mut() {
+            *time -= chrono::TimeDelta::seconds(reports[*index].entry_delay_seconds);
+        }
+
+        entries.sort();
+
+        let mut previous: Option<i64> = None;
+
+        for &(_, index) in entries.iter() {
+            let report = &mut reports[index];
+            let own = report.entry_delay_seconds;
+
+            let mut blended = match previous {
+                Some(previous) => {
+                    ((1.0 - correlation) * own as f32 + correlation * previous as f32).round()
+                        as i64
+                }
+                None => own,
+            };
+
+            if params.deny_early {
+                blended = blended.max(0);
+            }
+
+            if blended != own {
+                delay_file_entry(report, blended - own, params).with_context(|| {
+                    format!(
+                        "correlating delays, path: {}",
+                        report.file.to_string_lossy()
+                    )
+                })?;
+
+                report.entry_delay_seconds = blended;
+
+                log::debug!(
+                    "{}: entry delay {own} s blended to {blended} s",
+                    report.file.to_string_lossy()
+                );
+            }
+
+            previous = Some(blended);
+        }
+    }
+
+    Ok(())
+}
+
+/// The trains of `reports` by their entry station (`Betrst`), with their entry times and indices in `reports`. Cancelled and skipped trains and trains without an entry station or time are left out.
+fn entry_stations(
+    reports: &[FileReport],
+) -> Result<HashMap<String, Vec<(chrono::NaiveDateTime, usize)>>> {
+    let mut stations: HashMap<String, Vec<(chrono::NaiveDateTime, usize)>> = HashMap::new();
+
+    for (index, report) in reports.iter().enumerate() {
+        if report.cancelled || report.skipped {
+            continue;
+        }
+
+        let entry = entry_station(report)
+            .with_context(|| format!("reading entry, path: {}", report.file.to_string_lossy()))?;
+
+        if let Some((station, time)) = entry {
+            stations.entry(station).or_default().push((time, index));
+        }
+    }
+
+    Ok(stations)
+}
+
+/// The stations (`Betrst`) of all `FahrplanEintrag` of the train, in order. Stops without a station are left out.
+pub fn stations(tree: &Element) -> Result<Vec<String>> {
+    let zug = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    Ok(zug
+        .children
+        .iter()
+        .filter_map(|child| match child {
+            XMLNode::Element(e) if e.name == "FahrplanEintrag" => e.attributes.get("Betrst"),
+            _ => None,
+        })
+        .cloned()
+        .collect())
+}
+
+/// Note appended to the route by `note_entry_delay`, like ` (+5 min)`. Empty for delays rounding to 0 minutes.
+fn entry_delay_note(seconds: i64) -> String {
+    match (seconds as f32 / 60.0).round() as i64 {
+        0 => String::new(),
+        minutes => format!(" ({minutes:+} min)"),
+    }
+}
+
+/// Notes the entry delay in the route (`Zuglauf`) of the train, replacing the note of the `previous` entry delay.
+fn note_entry_delay(tree: &mut Element, previous: i64, seconds: i64) -> Result<()> {
+    let zug = tree
+        .get_mut_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let route = zug.attributes.entry("Zuglauf".to_owned()).or_default();
+    let without_previous = route
+        .strip_suffix(&entry_delay_note(previous))
+        .unwrap_or(route);
+
+    *route = format!("{without_previous}{}", entry_delay_note(seconds));
+
+    Ok(())
+}
+
+/// `route` without the note of the entry delay at its end, see `note_entry_delay`.
+fn without_entry_delay_note(route: &str) -> &str {
+    route
+        .strip_suffix(" min)")
+        .and_then(|rest| rest.rsplit_once(" ("))
+        .filter(|(_, minutes)| {
+            minutes.starts_with(['+', '-']) && minutes[1..].bytes().all(|b| b.is_ascii_digit())
+        })
+        .map_or(route, |(route, _)| route)
+}
+
+/// The route (`Zuglauf`) of the train, like `München Hbf - Amsterdam CS`, without the note of the entry delay modify may have added to it.
+pub fn route(tree: &Element) -> Option<&str> {
+    tree.get_child("Zug")
+        .and_then(|zug| zug.attributes.get("Zuglauf"))
+        .map(|route| without_entry_delay_note(route))
+}
+
+/// Whether the train has an arrival or departure on `date`, also if it started on the day before.
+pub fn runs_on(tree: &Element, date: chrono::NaiveDate) -> Result<bool> {
+    for [_, ankunft, abfahrt] in stop_times(tree) {
+        for time in ankunft.iter().chain(&abfahrt) {
+            if parse_time("time", time)?.date() == date {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// The arrival times (`Ank`) of all `FahrplanEintrag` of the train, in order.
+pub fn arrival_times(tree: &Element) -> Result<Vec<chrono::NaiveDateTime>> {
+    let zug = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    zug.children
+        .iter()
+        .filter_map(|child| match child {
+            XMLNode::Element(e) if e.name == "FahrplanEintrag" => e.attributes.get("Ank"),
+            _ => None,
+        })
+        .map(|ankunft| parse_time("arrival time", ankunft))
+        .collect()
+}
+
+/// Runs the lookups `modify_file` needs on a file, without changing it. Unknown tags in the consist are only an error if `strict`.
+pub fn verify_file(
+    path: &Path,
+    loc_pattern: Option<&Regex>,
+    loc_substrings: &[String],
+    strict: bool,
+) -> Result<()> {
+    let (tree, _) = read_file(path)?;
+    check_single_train(&tree)?;
+
+    let zug = tree
+        .get_child("Zug")
+        .ok_or(ModifyError::MissingTag("Zug"))?;
+
+    let mut unknown_tags = Vec::new();
+
+    let has_locomotive = train_has_locomotive(
+        &tree,
+        loc_pattern,
+        loc_substrings,
+        (!strict).then_some(&mut unknown_tags),
+    )?;
+
+    for tag in &unknown_tags {
+        log::warn!(
+            "{}: unknown tag `{tag}` in consist, treated as no locomotive",
+            path.to_string_lossy()
+        );
+    }
+
+    // Like `modify_file`, trains without a consist are still delayed, only their acceleration is left as it is.
+    if has_locomotive.is_none() {
+        log::warn!(
+            "{}: no consist (`FahrzeugVarianten`), the acceleration would not be modified",
+            path.to_string_lossy()
+        );
+    }
+
+    let apbeschl = zug
+        .attributes
+        .get("APBeschl")
+        .ok_or(ModifyError::MissingAcceleration)?;
+
+    parse_decimal("APBeschl", apbeschl)?;
+
+    // Modifying again multiplies the original acceleration instead, see `modify_multiplier`.
+    if let Some(original) = zug.attributes.get(ORIGINAL_ACCELERATION) {
+        parse_decimal(ORIGINAL_ACCELERATION, original)?;
+    }
+
+    if entry_time(&tree)?.is_none() {
+        return Err(ModifyError::MissingEntryTime);
+    }
+
+    for child in &zug.children {
+        let XMLNode::Element(e) = child else {
+            continue;
+        };
+
+        if e.name != "FahrplanEintrag" {
+            continue;
+        }
+
+        if let Some(ankunft) = e.attributes.get("Ank") {
+            parse_time("arrival time", ankunft)?;
+        }
+
+        if let Some(abfahrt) = e.attributes.get("Abf") {
+            parse_time("departure time", abfahrt)?;
+        }
+    }
+
+    Ok(())
+}
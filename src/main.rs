@@ -1,15 +1,38 @@
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::OsStr,
+    fmt::Write,
     fs::{self, File},
-    path::{Path, PathBuf},
+    io::{self, IsTerminal, Read, Write as _},
+    num::NonZeroUsize,
+    path::{Component, Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+    time::SystemTime,
 };
 
 use anyhow::{bail, Context};
-use clap::{Parser, Subcommand};
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use filetime::FileTime;
 use fs_extra::dir;
-use rand::Rng;
-use rand_distr::Distribution;
-use xmltree::{Element, XMLNode};
+use glob::Pattern;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use zusischewe::{
+    arrival_times, copy_name, correlate_delays, enforce_headway, entry_delay_minutes, modify_file,
+    named_folder, parse_date_time, parse_time_of_day, read_file, train_has_locomotive, verify_file,
+    weather_intensity, DelayType, DelayUnit, FileReport, ModifyError, ModifyParams,
+    CANCELLED_FOLDER, TIME_FORMAT,
+};
 
 /// ZuSi schlechtes Wetter
 ///
@@ -19,408 +42,2744 @@ use xmltree::{Element, XMLNode};
 struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    /// Do not print errors of single files. The command still fails if it does not succeed.
+    #[arg(short, long, global = true, action, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print each file as it is processed. Given twice, also print each modification applied to it.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     #[command(visible_alias = "m")]
-    Modify(Modify),
+    Modify(Box<Modify>),
     #[command(visible_alias = "r")]
     Reset(Reset),
+    #[command(visible_alias = "s")]
+    Snapshot(Snapshot),
+    #[command(visible_alias = "v")]
+    Verify(Verify),
+    #[command(visible_alias = "ls")]
+    ListStations(ListStations),
+    #[command(visible_alias = "i")]
+    Info(Info),
+    #[command(visible_alias = "pd")]
+    PreviewDistribution(Box<PreviewDistribution>),
 }
 
 /// Modify the acceleration of all trains.
 #[derive(Debug, Parser)]
 struct Modify {
     /// Path of the folder containing the timetable files. This folder should contain '.trn' and '.timetable.xml' files.
+    ///
+    /// It can also be a '.zip' archive, which is extracted, modified and packed again. The original archive is backed up next to it as `NAME_zsw.zip` instead of into a `_zsw` folder.
+    #[arg(
+        required_unless_present = "list_presets",
+        default_value = ".",
+        hide_default_value = true
+    )]
     directory: PathBuf,
 
-    /// Multiply the acceleration/deceleration of all trains by this factor.
+    /// Read arguments from this TOML file, like `friction = 0.2` or `delay-probability = 0.3`. Arguments given on the command line take precedence.
     ///
-    /// This affects the `APBeschl` property of trains.
-    #[arg(short = 'm', long)]
-    multiplier: Option<f32>,
+    /// Keys are the long names of the arguments. Unknown keys are an error. Relative station factors paths are relative to the folder of the file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Start from the arguments of a weather preset. The config file and the command line take precedence.
+    ///
+    /// Built-in presets are `light-snow`, `heavy-snow`, `fog` and `storm`. User presets are TOML files written like a config file, `NAME.toml` in `zusischewe/presets` in the config folder of the user: `%APPDATA%` on Windows, `$XDG_CONFIG_HOME` or `~/.config` elsewhere. A user preset takes precedence over a built-in one of the same name.
+    #[arg(long)]
+    preset: Option<String>,
+    /// Print the built-in and user presets, without modifying anything.
+    #[arg(long, action, exclusive = true)]
+    list_presets: bool,
+
+    #[command(flatten)]
+    params: ModifyParams,
 
-    /// Modify train acceleration/deceleration assuming this is the coeffient of friction.
+    /// Multiply the acceleration/deceleration of trains of a type by a factor, like `ICE=0.9`. Can be given multiple times.
     ///
-    /// This affects the `APBeschl` property of trains.
+    /// Types are matched on the `Gattung` attribute. The factor applies on top of the friction model and the multiplier, types not given use 1.0.
+    #[arg(visible_alias = "tm", long, value_parser = parse_type_multiplier)]
+    type_multiplier: Vec<(String, f32)>,
+
+    /// Path of a TOML file mapping station names to departure delay factors, e.g. `"Köln Hbf" = 6.0`.
+    ///
+    /// Stations are matched on the `Betrst` attribute. Stations not in the file use the departures delay factor.
+    #[arg(visible_alias = "sf", long)]
+    station_factors: Option<PathBuf>,
+    /// Name of a congested station, whose departures are delayed by the congested factor. Can be given multiple times.
     ///
-    /// The new `APBeschl` of the train is A*min(μ/M, 1) where Α is the old `APBeschl` value, μ is the new coefficient of friction, M is the coefficient of friction needed for the train to achieve full acceleration (see arguments loc_needed and mu_needed).
-    #[arg(short = 'f', long, default_value = "0.4")]
-    friction: f32,
-    /// Coefficient of friction needed for locomotives to achieve full acceleration/deceleration.
+    /// Stations are matched on the `Betrst` attribute, ignoring surrounding whitespace. Congested stations take precedence over the station factors file.
+    #[arg(visible_alias = "cs", long)]
+    congested_station: Vec<String>,
+    /// Departures delay factor of congested stations.
+    #[arg(visible_alias = "cf", long, default_value = "2")]
+    congested_factor: f32,
+
+    /// Minimum seconds between trains entering at the same station, after all other delays.
     ///
-    /// See the help of the friction argument for details.
-    #[arg(short = 'l', long, default_value = "0.4")]
-    loc_needed: f32,
-    /// Coefficient of friction needed for multiple units to achieve full acceleration/deceleration.
+    /// Trains entering at the same station (the `Betrst` of their first stop) are sorted by entry time, and the later of two trains entering closer than this is delayed further, so delayed trains do not pass through each other. Only modified trains are considered.
+    #[arg(long)]
+    enforce_headway: Option<u32>,
+    /// Share of the entry delay of the train entering before at the same station that a train gets, from 0 to 1.
     ///
-    /// See the help of the friction argument for details.
-    #[arg(short = 't', long, default_value = "0.25")]
-    mu_needed: f32,
+    /// Trains entering at the same station are sorted by their original entry time, and each gets this share of the delay of the one before, after blending, and the rest of its own. A late train so makes the trains after it late too, instead of all delays being independent. Applied before the headway.
+    #[arg(long)]
+    delay_correlation: Option<f32>,
 
-    /// Delay type A: probability of delay. Passing this argument applies delay type A.
+    /// Only modify trains whose file name matches this regex.
     ///
-    /// Delay type A delays the entry of trains by A(exp(μr)-1) where A is the amplitude and r is a random real in the interval [0, 1).
-    #[arg(visible_alias = "dp", long)]
-    delay_probability: Option<f32>,
-    /// Delay type A: amplitude of delay.
-    #[arg(visible_alias = "da", long, default_value = "360")]
-    delay_amplitude: f32,
-    /// Delay type A: λ parameter of delay.
-    #[arg(visible_alias = "dl", long, default_value = "3")]
-    delay_lambda: f32,
-
-    /// Delay type B: mean delay in minutes. Passing this argument applies delay type B.
+    /// The regex is matched against the file name, e.g. `CNL418.trn`. ZuSi names train files after the train's `Gattung` and `Nummer`, so `^RB` selects all regional trains. Other files are still backed up, but not modified.
+    #[arg(long)]
+    train_filter: Option<Regex>,
+    /// Do not modify trains whose file name matches this regex.
     ///
-    /// Delay type B delays the entry of trains according to a normal distribution.
-    #[arg(visible_alias = "bm", long)]
-    bell_mean: Option<f32>,
-    /// Delay type B: stardard deviation of delay in minutes.
-    #[arg(visible_alias = "bd", long, default_value = "5")]
-    bell_deviation: f32,
+    /// Matched like the train filter. A train matching both is not modified.
+    #[arg(long)]
+    train_filter_exclude: Option<Regex>,
 
-    /// Do not let the train enter early.
-    #[arg(short, long, action)]
-    deny_early: bool,
+    /// Only modify trains whose route (`Zuglauf`) matches this regex, like `Köln.*Düsseldorf`.
+    ///
+    /// The route is shown in ZuSi as the run of the train, from its first to its last station. Trains on other routes are still backed up, but not modified. A note of the entry delay at its end, see entry delay note, is not matched.
+    #[arg(long)]
+    route: Option<Regex>,
+    /// Only modify trains with an arrival or departure on this service day, like `2016-05-20`.
+    ///
+    /// Scenarios may contain trains of several days, of which only the one simulated should get the weather. Trains of other days are still backed up, but not modified.
+    #[arg(long)]
+    date: Option<chrono::NaiveDate>,
+
+    /// Only modify the first this many trains, in the order of their file names, after applying the filters.
+    ///
+    /// All files are still backed up. With a seed, the same trains get the same modifications as in a full run, so this allows quickly trying arguments on a few trains.
+    #[arg(long)]
+    max_files: Option<NonZeroUsize>,
+
+    /// Number of files modified in parallel. Defaults to the number of logical CPUs.
+    ///
+    /// With 1, files are modified one after the other, in order.
+    #[arg(short = 'j', long)]
+    jobs: Option<NonZeroUsize>,
+    /// Stop at the first file that fails, instead of modifying the others and reporting how many failed.
+    ///
+    /// Files modified before the failure stay modified, reset restores them.
+    #[arg(long, action)]
+    fail_fast: bool,
+
+    /// Do not modify files whose name matches this glob pattern, like `Rangier*.trn`. Can be given multiple times.
+    ///
+    /// Excluded files are still backed up.
+    #[arg(short = 'x', long)]
+    exclude_file: Vec<Pattern>,
+
+    /// Seed of the random number generator. A random seed is used if not given.
+    ///
+    /// Trains get the same delays with the same seed, no matter which other trains are modified. Trains are told apart by their `Gattung` and `Nummer`.
+    #[arg(long)]
+    seed: Option<u64>,
 
-    /// Delay trains as if passengers took a constant factor times longer to board.
-    #[arg(visible_alias = "dfac", long, default_value = "1")]
-    departures_delay_factor: f32,
-    /// Maximum delay of non-entry departures in minutes.
-    #[arg(visible_alias = "dmd", long, default_value = "6")]
-    departures_max_delay: f32,
+    /// Print statistics of the entry and departure delays of all modified trains.
+    #[arg(long, action)]
+    stats: bool,
+
+    /// Also modify the trains in subfolders of the folder, and in their subfolders.
+    ///
+    /// The backup mirrors the whole folder anyway, so reset restores subfolders as well. Backup folders and the folders of cancelled trains are skipped.
+    #[arg(short = 'R', long, action)]
+    recursive: bool,
+
+    /// Write a report of the modifications made to each file to this path.
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Format of the report: a table to read, a JSON array of the full reports, or CSV with a row for each file.
+    #[arg(long, value_enum, default_value = "human")]
+    report_format: ReportFormat,
+    /// Write a CSV file of the times of each modified stop to this path.
+    ///
+    /// It has a row for each `FahrplanEintrag` whose times were changed, with the train, its station and the original and new arrival and departure times.
+    #[arg(long)]
+    csv: Option<PathBuf>,
 
     /// Do not create `_zsw` folder used for resetting.
     #[arg(short = 'n', long, action)]
     no_copy: bool,
+    /// Back up into this folder instead of the `_zsw` folder next to the modified folder.
+    ///
+    /// Pass the same folder to reset to restore from it. Unlike the `_zsw` folder, which is reused if it exists, a backup folder that is not empty is an error unless force is given. It must be outside the modified folder.
+    #[arg(short = 'b', long)]
+    backup_dir: Option<PathBuf>,
+    /// Keep a stack of backups, so modifications can be undone one at a time.
+    ///
+    /// If the backup folder already exists, the files are backed up into the next free numbered folder next to it, like `_zsw.1`, `_zsw.2` and so on, before modifying them. The backup folder itself keeps the original files. See the steps argument of reset.
+    ///
+    /// Stacked modifications add up their delays, but not their acceleration: it is always multiplied from the original value in `APBeschlOrig`, so the last modification replaces that of the earlier ones.
+    #[arg(long, action, conflicts_with = "no_copy")]
+    stack: bool,
+    /// Back up into a compressed `.tar.zst` archive next to the backup folder, like `_zsw.tar.zst`, instead of copying the files.
+    ///
+    /// Reset restores from the archive if there is no backup folder. Like the `_zsw` folder, an existing archive is reused.
+    #[arg(long, action, conflicts_with_all = ["no_copy", "stack"])]
+    backup_compress: bool,
+    /// Write the modified folder to this folder instead, leaving the folder untouched.
+    ///
+    /// All files are copied, so the output is a complete folder to use in ZuSi. No backup is made, as the original files stay where they are. An output folder that is not empty is an error unless force is given.
+    #[arg(short, long, conflicts_with_all = ["backup_dir", "stack", "backup_compress"])]
+    output: Option<PathBuf>,
+    /// Only back up the trains that are modified, after the filters, with their timetables, instead of the whole folder.
+    ///
+    /// Trains that turn out unchanged, like those skipped by a skip window or the sim start, are dropped from the backup after modifying. The manifest of the backup lists the remaining files, and reset only restores them, leaving the other files as they are. A sparse backup can not be reused, so reset before modifying again.
+    #[arg(long, action, conflicts_with_all = ["no_copy", "stack", "backup_compress", "output", "summary_only"])]
+    sparse_backup: bool,
+    /// Only print the statistics of the modifications, without changing any files.
+    ///
+    /// The trains are modified in memory only, with the same random draws. Neither a backup nor the state file are written, but reports and CSV files are.
+    #[arg(long, action, conflicts_with_all = ["no_copy", "backup_dir", "stack", "backup_compress", "output"])]
+    summary_only: bool,
+    /// Keep the modification times of the modified files and their copies in the backup folder.
+    ///
+    /// Only the contents change then, which keeps folders that are synced by modification time from being uploaded again.
+    #[arg(long, action)]
+    preserve_mtime: bool,
+    /// Overwrite the contents of a backup folder that is not empty, and modify a folder that was already modified.
+    ///
+    /// Modifying a folder again compounds the delays, so it is refused without this unless stack is given. Modified folders are recognized by the `.zsw-state.json` file modify writes into them, which reset removes. The acceleration does not compound, as it is always multiplied from its original value in `APBeschlOrig`.
+    #[arg(long, action)]
+    force: bool,
+}
+
+/// Back up a folder without modifying it, to restore it with reset later.
+///
+/// Without a name, this is the `_zsw` folder modify backs up into, which modify then keeps as the backup of the original files. Named snapshots are kept next to it, like `Scenario_before-edit_zsw` for the name `before-edit`, and are restored by passing the name to reset.
+#[derive(Debug, Parser)]
+struct Snapshot {
+    directory: PathBuf,
+
+    /// Name of the snapshot.
+    name: Option<String>,
+
+    /// Overwrite the contents of a snapshot folder that is not empty.
+    #[arg(long, action)]
+    force: bool,
 }
 
-/// Reset using the `_zsw` folder.
+/// Reset using the `_zsw` folder, or undo the last modifications of a backup stack.
 #[derive(Debug, Parser)]
 struct Reset {
+    /// The modified folder, or the modified '.zip' archive, which is replaced by its backup.
     directory: PathBuf,
+
+    /// Restore from this folder instead of the `_zsw` folder, see the backup folder of modify.
+    #[arg(short = 'b', long)]
+    backup_dir: Option<PathBuf>,
+    /// Restore from the snapshot of this name, see the snapshot command.
+    #[arg(long, conflicts_with = "backup_dir")]
+    snapshot: Option<String>,
+
+    /// Reset even if files were changed after the last modification, losing those changes.
+    #[arg(short, long, action)]
+    force: bool,
+
+    /// Undo this many modifications of a backup stack, see the stack argument of modify. Defaults to 1.
+    ///
+    /// Without a backup stack, only one modification can be undone, restoring the original files.
+    #[arg(long, conflicts_with = "all")]
+    steps: Option<NonZeroUsize>,
+    /// Undo all modifications of a backup stack, restoring the original files.
+    #[arg(long, action)]
+    all: bool,
+
+    /// Copy the files back instead of moving them, so the backup can be used to reset again.
+    ///
+    /// By default, the backup is used up by resetting, freeing its space. Backups of the modifications undone on top of it are still removed.
+    #[arg(long, action)]
+    keep_backup: bool,
+
+    /// Only print which files would be deleted, restored and overwritten, without changing anything.
+    #[arg(long, action)]
+    dry_run: bool,
 }
 
-fn is_wagon_locomotive(data_tag: &Element) -> anyhow::Result<bool> {
-    let wagon_location = data_tag
-        .attributes
-        .get("Dateiname")
-        .context("tag 'Datei' inside tag 'FahrzeugInfo' has no attribute 'Dateiname'")?;
+/// Check that all trains can be modified, without modifying them.
+///
+/// Reports files that can not be parsed or lack the tags and attributes modify needs. Exits with an error if any file fails.
+#[derive(Debug, Parser)]
+struct Verify {
+    directory: PathBuf,
 
-    Ok(wagon_location.contains("lok"))
+    /// Regex deciding which vehicles are locomotives, see modify.
+    #[arg(long)]
+    loc_pattern: Option<Regex>,
+    /// Vehicles containing this are locomotives, see modify.
+    #[arg(long, conflicts_with = "loc_pattern")]
+    locomotive_substring: Vec<String>,
+    /// Fail on tags in the consist that are not known, see modify.
+    #[arg(long, action)]
+    strict: bool,
 }
 
-fn consist_has_locomotive(consist: &Element) -> anyhow::Result<bool> {
-    for child in &consist.children {
-        let XMLNode::Element(element) = child else {
-            continue;
-        };
+/// Print the stations (`Betrst`) of all trains and how often trains stop there, sorted by name.
+///
+/// Names are printed exactly as in the files, for use in station factors files and congested stations.
+#[derive(Debug, Parser)]
+struct ListStations {
+    directory: PathBuf,
 
-        match element.name.as_str() {
-            "Datei" => {
-                if is_wagon_locomotive(element)? {
-                    return Ok(true);
-                }
-            }
-            "FahrzeugInfo" => {
-                let data = element
-                    .get_child("Datei")
-                    .context("tag 'FahrzeugInfo' has no tag 'Datei'")?;
+    /// Print as CSV with a header, instead of aligned columns.
+    #[arg(long, action)]
+    csv: bool,
+}
 
-                if is_wagon_locomotive(data)? {
-                    return Ok(true);
-                }
-            }
-            "FahrzeugVarianten" => {
-                if consist_has_locomotive(element)? {
-                    return Ok(true);
-                }
-            }
-            name => bail!("Unknown tag '{name}' inside tag 'FahrzeugVarianten' or 'FahrzeugInfo'"),
+/// Print an overview of the trains of a folder, without changing anything.
+///
+/// Shows the number of trains per type (`Gattung`), how many have a locomotive, the earliest and latest arrival and the stations they stop at.
+#[derive(Debug, Parser)]
+struct Info {
+    directory: PathBuf,
+
+    /// Regex matching the file names of locomotives, see the loc pattern argument of modify.
+    #[arg(long)]
+    loc_pattern: Option<Regex>,
+    /// Vehicles containing this are locomotives, see the locomotive substring argument of modify.
+    #[arg(long, conflicts_with = "loc_pattern")]
+    locomotive_substring: Vec<String>,
+}
+
+/// Print the distribution of the entry delays the delay arguments of modify produce, without modifying any files.
+///
+/// Delays are drawn like modify does for each train. Peak windows are ignored, as the samples have no entry time.
+#[derive(Debug, Parser)]
+struct PreviewDistribution {
+    #[command(flatten)]
+    params: ModifyParams,
+
+    /// Number of entry delays to draw.
+    #[arg(long, default_value = "10000")]
+    samples: NonZeroUsize,
+    /// Seed of the random number generator. A random seed is used if not given.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// Parses a type multiplier like `ICE=0.9`.
+fn parse_type_multiplier(s: &str) -> anyhow::Result<(String, f32)> {
+    let (gattung, factor) = s
+        .split_once('=')
+        .context("expected a type and a factor like `ICE=0.9`")?;
+
+    let factor = factor
+        .trim()
+        .parse()
+        .context(format!("parsing factor `{factor}`"))?;
+
+    Ok((gattung.trim().to_owned(), factor))
+}
+
+/// Format of the report of modify.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Human,
+    Json,
+    Csv,
+}
+
+/// Built-in weather, see `Preset::params`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Preset {
+    /// Slightly slippery rails and a few delayed trains.
+    LightSnow,
+    /// Slippery rails, most trains delayed and slow boarding.
+    HeavySnow,
+    /// Damp rails and cautious driving.
+    Fog,
+    /// Slippery rails and long delays from obstructed lines.
+    Storm,
+}
+
+impl Preset {
+    /// Delay type A of the presets delays trains by at most 6 (fog) to 57 (storm) minutes.
+    fn params(self) -> ModifyParams {
+        let default = ModifyParams::default();
+
+        match self {
+            Preset::LightSnow => ModifyParams {
+                friction: 0.3,
+                delay_probability: Some(0.3),
+                delay_amplitude: 1.0,
+                delay_lambda: 2.5,
+                departures_delay_factor: 1.2,
+                ..default
+            },
+            Preset::HeavySnow => ModifyParams {
+                friction: 0.15,
+                delay_probability: Some(0.7),
+                delay_amplitude: 2.0,
+                delay_lambda: 3.0,
+                departures_delay_factor: 1.5,
+                freight_departures_delay_factor: 1.3,
+                departures_max_delay: 10.0,
+                ..default
+            },
+            Preset::Fog => ModifyParams {
+                friction: 0.35,
+                delay_probability: Some(0.4),
+                delay_amplitude: 1.0,
+                delay_lambda: 2.0,
+                departures_delay_factor: 1.1,
+                ..default
+            },
+            Preset::Storm => ModifyParams {
+                friction: 0.25,
+                delay_probability: Some(0.6),
+                delay_amplitude: 3.0,
+                delay_lambda: 3.0,
+                departures_delay_factor: 1.3,
+                freight_departures_delay_factor: 1.2,
+                ..default
+            },
         }
     }
-
-    Ok(false)
 }
 
-/// `train` is XML tag `Zug`.
-fn is_passenger(train: &Element) -> bool {
-    let zugtyp = train.attributes.get("Zugtyp");
+/// Folder of user presets, `None` if the config folder of the user is unknown.
+fn presets_dir() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ if cfg!(windows) => PathBuf::from(std::env::var_os("APPDATA")?),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("zusischewe").join("presets"))
+}
 
-    let Some(train_type) = zugtyp else {
-        return false;
+/// User presets by name. Empty if there is no presets folder.
+fn user_presets() -> anyhow::Result<BTreeMap<String, PathBuf>> {
+    let Some(dir) = presets_dir().filter(|dir| dir.is_dir()) else {
+        return Ok(BTreeMap::new());
     };
 
-    *train_type == "1"
+    let mut presets = BTreeMap::new();
+
+    for entry in fs::read_dir(&dir)
+        .with_context(|| format!("reading presets folder, path: {}", dir.to_string_lossy()))?
+    {
+        let path = entry.context("reading presets folder")?.path();
+
+        if path.is_file() && path.extension() == Some(OsStr::new("toml")) {
+            if let Some(name) = path.file_stem() {
+                presets.insert(name.to_string_lossy().into_owned(), path);
+            }
+        }
+    }
+
+    Ok(presets)
 }
 
-fn modify_multiplier(
-    tree: &mut Element,
-    loc_multiplier: f32,
-    mu_multiplier: f32,
-) -> anyhow::Result<()> {
-    let train = tree.get_mut_child("Zug").context("no tag 'Zug'")?;
+/// Sets the arguments of `cmd` not given on the command line to those of preset `name`, a user preset if there is one.
+fn apply_named_preset(name: &str, cmd: &mut Modify, matches: &ArgMatches) -> anyhow::Result<()> {
+    let built_in = Preset::from_str(name, false).ok();
+
+    match (user_presets()?.remove(name), built_in) {
+        (Some(path), built_in) => {
+            if built_in.is_some() {
+                log::warn!(
+                    "User preset `{name}` replaces the built-in one, path: {}",
+                    path.to_string_lossy()
+                );
+            }
 
-    let consist = train
-        .get_child("FahrzeugVarianten")
-        .context("no tag 'FahrzeugVarianten'")?;
+            ModifyConfig::read(&path)
+                .with_context(|| format!("reading preset, path: {}", path.to_string_lossy()))?
+                .apply(cmd, matches)
+                .with_context(|| format!("applying preset, path: {}", path.to_string_lossy()))
+        }
+        (None, Some(preset)) => {
+            apply_preset(&mut cmd.params, preset.params(), matches);
+            Ok(())
+        }
+        (None, None) => bail!("unknown preset `{name}`, see `--list-presets`"),
+    }
+}
 
-    let has_locomotive = consist_has_locomotive(consist)
-        .context("trying to determine whether consist has a locomotive")?;
+/// Prints the names of the built-in presets with their descriptions, then those of the user presets with their paths.
+fn list_presets() -> anyhow::Result<()> {
+    let user_presets = user_presets()?;
 
-    let apbeschl = train
-        .attributes
-        .get_mut("APBeschl")
-        .context("no attribute 'APBeschl'")?;
+    println!("Built-in presets:");
 
-    let acceleration: f32 = apbeschl
-        .parse()
-        .with_context(|| "unable to parse `APBeschl`")?;
+    for preset in Preset::value_variants() {
+        let value = preset.to_possible_value().expect("presets are not skipped");
+        let replaced = match user_presets.contains_key(value.get_name()) {
+            true => " (replaced by user preset)",
+            false => "",
+        };
 
-    let multiplier = match has_locomotive {
-        true => loc_multiplier,
-        false => mu_multiplier,
-    };
+        match value.get_help() {
+            Some(help) => println!("  {}: {help}{replaced}", value.get_name()),
+            None => println!("  {}{replaced}", value.get_name()),
+        }
+    }
+
+    match presets_dir() {
+        Some(dir) => println!("User presets in `{}`:", dir.to_string_lossy()),
+        None => println!("User presets:"),
+    }
 
-    *apbeschl = (multiplier * acceleration).to_string();
+    if user_presets.is_empty() {
+        println!("  none");
+    }
+
+    for name in user_presets.keys() {
+        println!("  {name}");
+    }
 
     Ok(())
 }
 
-fn delay_entry(tree: &mut Element, seconds: u32) -> anyhow::Result<()> {
-    for child in &mut tree.get_mut_child("Zug").context("no tag `Zug`")?.children {
-        if let XMLNode::Element(e) = child {
-            if e.name == "FahrplanEintrag" {
-                let ankunft = e
-                    .attributes
-                    .get_mut("Ank")
-                    .context("no starting time: no attribute `Ank` on first `FahrplanEintrag`")?;
-
-                let arrival: chrono::NaiveDateTime =
-                    chrono::NaiveDateTime::parse_from_str(ankunft, "%Y-%m-%d %H:%M:%S")
-                        .context(format!("parsing arrival time `{ankunft}`"))?;
-                let delayed = arrival
-                    .checked_add_signed(chrono::TimeDelta::seconds(seconds as i64))
-                    .context("calculating new arrival time")?;
-                *ankunft = delayed.format("%Y-%m-%d %H:%M:%S").to_string();
-
-                return Ok(());
+/// Sets the arguments of `params` not given on the command line, according to `matches`, to those of `preset`.
+fn apply_preset(params: &mut ModifyParams, preset: ModifyParams, matches: &ArgMatches) {
+    macro_rules! apply {
+        ($($field:ident),* $(,)?) => {$(
+            if matches.value_source(stringify!($field)) != Some(ValueSource::CommandLine) {
+                params.$field = preset.$field;
             }
-        }
+        )*};
     }
 
-    bail!("no `FahrplanEintrag` entry inside `Zug`")
+    apply! {
+        multiplier,
+        accel_reduction_percent,
+        friction,
+        loc_needed,
+        mu_needed,
+        ratio_model,
+        min_acceleration,
+        speed_penalty,
+        speed_threshold,
+        strict,
+        no_multiplier_if_no_loco_info,
+        locomotive_substring,
+        loc_pattern,
+        delay_probability,
+        delay_amplitude,
+        delay_lambda,
+        bell_mean,
+        bell_deviation,
+        delay_bias,
+        gamma_shape,
+        gamma_scale,
+        delay_types,
+        delay_unit,
+        propagate_entry_delay,
+        entry_delay_note,
+        recovery_per_stop,
+        skip_window,
+        sim_start,
+        peak_window,
+        peak_multiplier,
+        ramp_start,
+        ramp_peak,
+        ramp_end,
+        cancel_probability,
+        advance,
+        advance_probability,
+        deny_early,
+        min_entry_delay,
+        max_entry_delay,
+        max_delay_fraction,
+        departures_delay_factor,
+        freight_departures_delay_factor,
+        departures_max_delay,
+        min_added_dwell,
+        no_terminus_dwell,
+        departure_jitter,
+        departures_delay_probability,
+        min_dwell,
+        round_to,
+        include_timetable,
+    }
 }
 
-fn delay_departures(
-    tree: &mut Element,
-    factor: f32,
-    max_wait_time: chrono::TimeDelta,
-) -> anyhow::Result<()> {
-    let zug = tree.get_mut_child("Zug").context("no tag `Zug`")?;
+/// Arguments of modify read from a config file. `None` if not in the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ModifyConfig {
+    multiplier: Option<f32>,
+    accel_reduction_percent: Option<f32>,
+    friction: Option<f32>,
+    loc_needed: Option<f32>,
+    mu_needed: Option<f32>,
+    ratio_model: Option<bool>,
+    min_acceleration: Option<f32>,
+    strict: Option<bool>,
+    no_multiplier_if_no_loco_info: Option<bool>,
+    locomotive_substring: Option<Vec<String>>,
+    speed_penalty: Option<f32>,
+    speed_threshold: Option<f32>,
+    loc_pattern: Option<String>,
+    delay_probability: Option<f32>,
+    delay_amplitude: Option<f32>,
+    delay_lambda: Option<f32>,
+    bell_mean: Option<f32>,
+    bell_deviation: Option<f32>,
+    delay_bias: Option<f32>,
+    gamma_shape: Option<f32>,
+    gamma_scale: Option<f32>,
+    delay_types: Option<Vec<DelayType>>,
+    delay_unit: Option<DelayUnit>,
+    propagate_entry_delay: Option<bool>,
+    entry_delay_note: Option<bool>,
+    recovery_per_stop: Option<u32>,
+    skip_window: Option<Vec<String>>,
+    sim_start: Option<String>,
+    peak_window: Option<Vec<String>>,
+    peak_multiplier: Option<f32>,
+    ramp_start: Option<String>,
+    ramp_peak: Option<String>,
+    ramp_end: Option<String>,
+    cancel_probability: Option<f32>,
+    advance: Option<f32>,
+    advance_probability: Option<f32>,
+    deny_early: Option<bool>,
+    min_entry_delay: Option<f32>,
+    max_entry_delay: Option<f32>,
+    max_delay_fraction: Option<f32>,
+    departures_delay_factor: Option<f32>,
+    freight_departures_delay_factor: Option<f32>,
+    departures_max_delay: Option<f32>,
+    min_added_dwell: Option<u32>,
+    no_terminus_dwell: Option<bool>,
+    departure_jitter: Option<u32>,
+    departures_delay_probability: Option<f32>,
+    min_dwell: Option<u32>,
+    round_to: Option<u32>,
+    include_timetable: Option<bool>,
+    /// Maps types to factors, unlike the `Gattung=factor` values of the argument.
+    type_multiplier: Option<HashMap<String, f32>>,
+    station_factors: Option<PathBuf>,
+    congested_station: Option<Vec<String>>,
+    congested_factor: Option<f32>,
+    enforce_headway: Option<u32>,
+    delay_correlation: Option<f32>,
+}
 
-    if !is_passenger(zug) {
-        return Ok(());
+impl ModifyConfig {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let mut config: ModifyConfig = toml::from_str(&fs::read_to_string(path)?)?;
+
+        if let (Some(station_factors), Some(folder)) = (&config.station_factors, path.parent()) {
+            config.station_factors = Some(folder.join(station_factors));
+        }
+
+        Ok(config)
     }
 
-    for child in &mut zug.children {
-        if let XMLNode::Element(e) = child {
-            if e.name == "FahrplanEintrag" {
-                // A demo implementation of modifying factor based on station.
-                //
-                // ```
-                // let betriebstelle = e.attributes.get("Betrst");
-                // let factor = match betriebstelle {
-                //     Some(str) => match str.as_str() {
-                //         "Köln Hbf" => 6.0,
-                //         "Köln Messe/Deutz Hp" => 4.5,
-                //         _ => factor,
-                //     }
-                //     _ => factor,
-                // };
-                // ```
-
-                let Some(ankunft) = e.attributes.get("Ank") else {
-                    continue;
-                };
-                let ankunft = ankunft.clone();
-
-                let Some(abfahrt) = e.attributes.get_mut("Abf") else {
-                    continue;
-                };
-
-                let arrival: chrono::NaiveDateTime =
-                    chrono::NaiveDateTime::parse_from_str(&ankunft, "%Y-%m-%d %H:%M:%S")
-                        .context(format!("parsing arrival time `{ankunft}`"))?;
-
-                let departure: chrono::NaiveDateTime =
-                    chrono::NaiveDateTime::parse_from_str(abfahrt, "%Y-%m-%d %H:%M:%S")
-                        .context(format!("parsing departure time `{abfahrt}`"))?;
-
-                let original_wait_time = departure - arrival;
-                let delayed_wait_time = chrono::TimeDelta::seconds(
-                    (original_wait_time.num_seconds() as f32 * factor) as i64,
-                )
-                .min(max_wait_time);
+    /// Sets the arguments of `cmd` not given on the command line, according to `matches`, to the values in the config.
+    fn apply(self, cmd: &mut Modify, matches: &ArgMatches) -> anyhow::Result<()> {
+        let from_command_line =
+            |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! apply {
+            ($($target:expr => $field:ident),* $(,)?) => {$(
+                if let Some(value) = self.$field {
+                    if !from_command_line(stringify!($field)) {
+                        $target = value
+                            .try_into()
+                            .with_context(|| format!("parsing `{}`", stringify!($field).replace('_', "-")))?;
+                    }
+                }
+            )*};
+        }
+
+        let params = &mut cmd.params;
+
+        apply! {
+            params.multiplier => multiplier,
+            params.accel_reduction_percent => accel_reduction_percent,
+            params.friction => friction,
+            params.loc_needed => loc_needed,
+            params.mu_needed => mu_needed,
+            params.ratio_model => ratio_model,
+            params.min_acceleration => min_acceleration,
+            params.strict => strict,
+            params.no_multiplier_if_no_loco_info => no_multiplier_if_no_loco_info,
+            params.locomotive_substring => locomotive_substring,
+            params.speed_penalty => speed_penalty,
+            params.speed_threshold => speed_threshold,
+            params.delay_probability => delay_probability,
+            params.delay_amplitude => delay_amplitude,
+            params.delay_lambda => delay_lambda,
+            params.bell_mean => bell_mean,
+            params.bell_deviation => bell_deviation,
+            params.delay_bias => delay_bias,
+            params.gamma_shape => gamma_shape,
+            params.gamma_scale => gamma_scale,
+            params.delay_types => delay_types,
+            params.delay_unit => delay_unit,
+            params.propagate_entry_delay => propagate_entry_delay,
+            params.entry_delay_note => entry_delay_note,
+            params.recovery_per_stop => recovery_per_stop,
+            params.peak_multiplier => peak_multiplier,
+            params.cancel_probability => cancel_probability,
+            params.advance => advance,
+            params.advance_probability => advance_probability,
+            params.deny_early => deny_early,
+            params.min_entry_delay => min_entry_delay,
+            params.max_entry_delay => max_entry_delay,
+            params.max_delay_fraction => max_delay_fraction,
+            params.departures_delay_factor => departures_delay_factor,
+            params.freight_departures_delay_factor => freight_departures_delay_factor,
+            params.departures_max_delay => departures_max_delay,
+            params.min_added_dwell => min_added_dwell,
+            params.no_terminus_dwell => no_terminus_dwell,
+            params.departure_jitter => departure_jitter,
+            params.departures_delay_probability => departures_delay_probability,
+            params.min_dwell => min_dwell,
+            params.round_to => round_to,
+            params.include_timetable => include_timetable,
+            cmd.station_factors => station_factors,
+            cmd.congested_station => congested_station,
+            cmd.congested_factor => congested_factor,
+            cmd.enforce_headway => enforce_headway,
+            cmd.delay_correlation => delay_correlation,
+        }
+
+        if let Some(loc_pattern) = self.loc_pattern {
+            if !from_command_line("loc_pattern") {
+                cmd.params.loc_pattern =
+                    Some(Regex::new(&loc_pattern).context("parsing `loc-pattern`")?);
+            }
+        }
+
+        if let Some(skip_window) = self.skip_window {
+            if !from_command_line("skip_window") {
+                cmd.params.skip_window = skip_window
+                    .iter()
+                    .map(|window| window.parse())
+                    .collect::<Result<_, ModifyError>>()
+                    .context("parsing `skip-window`")?;
+            }
+        }
+
+        if let Some(sim_start) = self.sim_start {
+            if !from_command_line("sim_start") {
+                cmd.params.sim_start =
+                    Some(parse_date_time(&sim_start).context("parsing `sim-start`")?);
+            }
+        }
 
-                let delayed_departure = departure
-                    .checked_add_signed(delayed_wait_time)
-                    .context("calculating new arrival time")?;
+        if let Some(peak_window) = self.peak_window {
+            if !from_command_line("peak_window") {
+                cmd.params.peak_window = peak_window
+                    .iter()
+                    .map(|window| window.parse())
+                    .collect::<Result<_, ModifyError>>()
+                    .context("parsing `peak-window`")?;
+            }
+        }
+
+        for (id, time, target) in [
+            ("ramp_start", self.ramp_start, &mut cmd.params.ramp_start),
+            ("ramp_peak", self.ramp_peak, &mut cmd.params.ramp_peak),
+            ("ramp_end", self.ramp_end, &mut cmd.params.ramp_end),
+        ] {
+            if let Some(time) = time {
+                if !from_command_line(id) {
+                    *target = Some(
+                        parse_time_of_day(&time)
+                            .with_context(|| format!("parsing `{}`", id.replace('_', "-")))?,
+                    );
+                }
+            }
+        }
 
-                *abfahrt = delayed_departure.format("%Y-%m-%d %H:%M:%S").to_string();
+        if let Some(type_multiplier) = self.type_multiplier {
+            if !from_command_line("type_multiplier") {
+                cmd.type_multiplier = type_multiplier
+                    .into_iter()
+                    .map(|(gattung, factor)| (gattung.trim().to_owned(), factor))
+                    .collect();
             }
         }
+
+        Ok(())
+    }
+}
+
+/// Fails if the friction arguments of `params` would make the acceleration multipliers infinite or not a number, or if more than one way of reducing the acceleration is given.
+fn validate_friction(params: &ModifyParams) -> anyhow::Result<()> {
+    if !(params.friction >= 0.0 && params.friction.is_finite()) {
+        bail!(
+            "`--friction` must be a non-negative number, got {}",
+            params.friction
+        );
+    }
+
+    for (name, needed) in [
+        ("loc-needed", params.loc_needed),
+        ("mu-needed", params.mu_needed),
+    ] {
+        if !(needed > 0.0 && needed.is_finite()) {
+            bail!("`--{name}` must be positive, got {needed}");
+        }
+    }
+
+    if let Some(multiplier) = params.multiplier {
+        if !multiplier.is_finite() {
+            bail!("`--multiplier` must be a finite number, got {multiplier}");
+        }
+    }
+
+    if let Some(percent) = params.accel_reduction_percent {
+        if !(0.0..=100.0).contains(&percent) {
+            bail!("`--accel-reduction-percent` must be between 0 and 100, got {percent}");
+        }
+
+        // Only one can be given on the command line, but a config or preset may add the other.
+        if params.multiplier.is_some() {
+            bail!(
+                "`--accel-reduction-percent` replaces `--multiplier`, only one of them can be used"
+            );
+        }
     }
 
     Ok(())
 }
 
-fn read_file(path: &Path) -> anyhow::Result<Element> {
+fn read_station_factors(path: &Path) -> anyhow::Result<HashMap<String, f32>> {
     let contents = fs::read_to_string(path)?;
+    let station_factors: HashMap<String, f32> = toml::from_str(&contents)?;
+
+    Ok(station_factors
+        .into_iter()
+        .map(|(station, factor)| (station.trim().to_owned(), factor))
+        .collect())
+}
+
+/// The folder of the snapshot `name` of `dir`, see `Snapshot`. The `_zsw` folder without a name.
+fn snapshot_path(dir: &Path, name: Option<&str>) -> anyhow::Result<PathBuf> {
+    let copy = copy_name(dir)?;
 
-    Ok(Element::parse(contents.as_bytes())?)
+    let Some(name) = name else {
+        return Ok(copy);
+    };
+
+    if name.is_empty() || name.contains(['/', '\\']) {
+        bail!("snapshot name `{name}` must be a non-empty file name");
+    }
+
+    let (_, mut file_name) = named_folder(dir)?;
+    file_name.push(format!("_{name}_zsw"));
+
+    Ok(copy.with_file_name(file_name))
 }
 
-fn write_file(path: &Path, tree: Element) -> anyhow::Result<()> {
-    tree.write(File::create(path)?)?;
+/// Copies the files inside `directory` into the backup folder `to`, which must be empty unless `force`.
+fn copy_to_backup(directory: &Path, to: &Path, force: bool) -> anyhow::Result<()> {
+    if to.exists() && !force && fs::read_dir(to)?.next().is_some() {
+        bail!(
+            "backup folder `{}` is not empty, pass `--force` to overwrite it",
+            to.to_string_lossy()
+        );
+    }
+
+    dir::create(to, true).context("creating backup folder")?;
+    dir::copy(directory, to, &dir::CopyOptions::new().content_only(true))
+        .context("copying files to backup folder")?;
 
     Ok(())
 }
 
-fn modify_file(
-    path: &Path,
-    modify: &Modify,
-    rng: &mut rand::rngs::ThreadRng,
-) -> anyhow::Result<()> {
-    let mut tree = read_file(path)?;
+fn snapshot(cmd: Snapshot) -> anyhow::Result<()> {
+    if !cmd.directory.is_dir() {
+        bail!("`{}` is not a folder", cmd.directory.to_string_lossy());
+    }
 
-    // multiplier
-    {
-        let mut loc_multiplier = (modify.friction / modify.loc_needed).min(1.0);
-        let mut mu_multiplier = (modify.friction / modify.mu_needed).min(1.0);
+    let to = snapshot_path(&cmd.directory, cmd.name.as_deref())?;
 
-        if let Some(multiplier) = modify.multiplier {
-            loc_multiplier *= multiplier;
-            mu_multiplier *= multiplier;
-        }
+    copy_to_backup(&cmd.directory, &to, cmd.force)
+}
 
-        // This is only here to not try to perform an unneeded operation if no changes are needed. If friction >= *_needed, then *_multiplier = 1.0, so this check is enough.
-        if loc_multiplier != 1.0 || mu_multiplier != 1.0 {
-            modify_multiplier(&mut tree, loc_multiplier, mu_multiplier)
-                .context("applying multiplier")?;
+/// The backup folder of `dir`, which is `backup_dir` if given. Fails if `backup_dir` is inside `dir`, see `check_backup_outside`.
+fn backup_path(dir: &Path, backup_dir: Option<&Path>) -> anyhow::Result<PathBuf> {
+    match backup_dir {
+        Some(backup_dir) => {
+            check_backup_outside(dir, backup_dir)?;
+            Ok(backup_dir.to_path_buf())
         }
+        None => Ok(copy_name(dir)?),
     }
+}
 
-    // delay entry
-    {
-        let mut minutes: f32 = 0.0;
+/// Fails if `backup` is `dir` or inside it. Modify would copy the folder into its own backup, and reset would delete the backup when emptying the folder.
+fn check_backup_outside(dir: &Path, backup: &Path) -> anyhow::Result<()> {
+    let dir = resolve_path(dir).context("resolving folder")?;
+    let resolved = resolve_path(backup).context("resolving backup folder")?;
+
+    if resolved.starts_with(&dir) {
+        bail!(
+            "backup folder `{}` is inside `{}`, choose a folder outside of it",
+            backup.to_string_lossy(),
+            dir.to_string_lossy()
+        );
+    }
+
+    Ok(())
+}
+
+/// `path` canonicalized, also if it does not exist yet, by canonicalizing the closest of its parents that does.
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    if path.exists() {
+        return path.canonicalize();
+    }
+
+    let absolute = std::path::absolute(path)?;
+
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => Ok(resolve_path(parent)?.join(name)),
+        _ => Ok(absolute),
+    }
+}
+
+/// `message` followed by the reason and contexts of `err`, one per line.
+fn error_chain(message: &str, err: &(dyn std::error::Error + 'static)) -> String {
+    let chain: Vec<_> = std::iter::successors(Some(err), |err| err.source()).collect();
+
+    // Built up front and printed at once, so messages of files processed in parallel do not interleave.
+    let mut out = format!("{message}\n| reason: {}", chain[chain.len() - 1]);
+
+    for context in chain.iter().rev().skip(1) {
+        let _ = write!(out, "\n| when: {context}");
+    }
+
+    out
+}
+
+fn print_error(message: &str, err: &anyhow::Error) {
+    eprintln!("{}", error_chain(message, err.as_ref()));
+}
 
-        if let Some(p) = modify.delay_probability {
-            let val: f32 = rng.gen();
+/// Whether `name` is the name of a `_zsw` folder or of a folder of a backup stack on top of it.
+fn is_backup_folder_name(name: &str) -> bool {
+    name.ends_with("_zsw")
+        || name
+            .rsplit_once("_zsw.")
+            .is_some_and(|(_, level)| level.parse::<usize>().is_ok())
+}
+
+/// All `.trn` files of `directory`, and of its subfolders if `recursive`, sorted by file name. Fails if there are none, so nothing is backed up or modified in a wrong folder.
+///
+/// Backup folders and the folders of cancelled trains are skipped, so backups are not modified again. A backup folder given to modify is never inside `directory`, see `check_backup_outside`.
+fn train_files(directory: &Path, recursive: bool) -> anyhow::Result<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        bail!("`{}` is not a folder", directory.to_string_lossy());
+    }
 
-            if val < p {
-                minutes +=
-                    modify.delay_amplitude * ((modify.delay_lambda * rng.gen::<f32>()).exp() - 1.0);
+    let walker = WalkDir::new(directory)
+        .min_depth(1)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .into_iter()
+        .filter_entry(|entry| {
+            if !entry.file_type().is_dir() {
+                return true;
             }
-        }
 
-        if let Some(bell_mean) = modify.bell_mean {
-            minutes += rand_distr::Normal::new(bell_mean, modify.bell_deviation)
-                .context("unable to generate normal distribution for random number sampling with given parameters")?
-                .sample(rng);
-        }
+            let name = entry.file_name().to_string_lossy();
+
+            !(name == CANCELLED_FOLDER || is_backup_folder_name(&name))
+        });
 
-        if modify.deny_early {
-            minutes = minutes.max(0.0);
+    let mut paths = Vec::new();
+
+    for entry in walker {
+        let entry = entry.context("reading folder")?;
+        let path = entry.path();
+
+        if entry.file_type().is_file() && path.extension() == Some(OsStr::new("trn")) {
+            paths.push(path.to_path_buf());
         }
+    }
+
+    // Sorted, so files are processed and reported in the same order on all platforms.
+    paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()).then_with(|| a.cmp(b)));
+
+    if paths.is_empty() {
+        bail!(
+            "`{}` contains no '.trn' files, is this the right folder?",
+            directory.to_string_lossy()
+        );
+    }
+
+    Ok(paths)
+}
 
-        let seconds = (minutes * 60.0) as u32;
+/// `matches` are those of the modify command, used to tell which arguments were given on the command line.
+fn modify(mut cmd: Modify, matches: &ArgMatches) -> anyhow::Result<()> {
+    if let Some(name) = cmd.preset.clone() {
+        apply_named_preset(&name, &mut cmd, matches)?;
+    }
+
+    if let Some(path) = cmd.config.clone() {
+        ModifyConfig::read(&path)
+            .with_context(|| format!("reading config, path: {}", path.to_string_lossy()))?
+            .apply(&mut cmd, matches)
+            .with_context(|| format!("applying config, path: {}", path.to_string_lossy()))?;
+    }
+
+    validate_friction(&cmd.params)?;
 
-        if seconds != 0 {
-            delay_entry(&mut tree, seconds).context("delaying entry")?;
+    if let Some(correlation) = cmd.delay_correlation {
+        if !(0.0..=1.0).contains(&correlation) {
+            bail!("`--delay-correlation` must be between 0 and 1, got {correlation}");
         }
     }
+    // Checks the ramp once, instead of failing each file.
+    weather_intensity(&cmd.params, None)?;
+
+    cmd.params.station_factors = match &cmd.station_factors {
+        Some(path) => read_station_factors(path).with_context(|| {
+            format!("reading station factors, path: {}", path.to_string_lossy())
+        })?,
+        None => HashMap::new(),
+    };
 
-    // delay_departure
-    if modify.departures_delay_factor != 1.0 {
-        delay_departures(
-            &mut tree,
-            modify.departures_delay_factor,
-            chrono::TimeDelta::seconds((modify.departures_max_delay * 60.0) as i64),
-        )
-        .context("delaying departures")?;
+    for station in &cmd.congested_station {
+        cmd.params
+            .station_factors
+            .insert(station.trim().to_owned(), cmd.congested_factor);
     }
 
-    write_file(path, tree)?;
+    cmd.params.type_multipliers = cmd.type_multiplier.iter().cloned().collect();
 
-    Ok(())
-}
+    // Read before copying, so an already modified folder leaves no output folder behind. The copy gets the same state.
+    let state_path = cmd.directory.join(STATE_FILE);
+    let state = match state_path.exists() {
+        true => State::read(&state_path)
+            .with_context(|| format!("reading state, path: {}", state_path.to_string_lossy()))?,
+        false => State::default(),
+    };
 
-fn copy_name(dir: &Path) -> Option<PathBuf> {
-    let mut file_name = dir.file_name()?.to_os_string();
-    file_name.push("_zsw");
-    Some(dir.with_file_name(file_name))
-}
+    if let Some(last) = state.runs.last() {
+        if !(cmd.force || cmd.stack) {
+            bail!(
+                "`{}` was already modified at {}, modifying it again compounds the modifications; reset it first, or pass `--stack` or `--force` to modify it again",
+                cmd.directory.to_string_lossy(),
+                last.time
+            );
+        }
+    }
 
-fn modify(cmd: Modify) {
-    let to = copy_name(&cmd.directory);
+    // The copy is modified like the folder itself, without backup.
+    if let Some(output) = cmd.output.take() {
+        // Checked before copying, so a wrong folder is neither copied nor leaves an output folder behind.
+        train_files(&cmd.directory, cmd.recursive)?;
 
-    if !(cmd.no_copy || to.as_ref().unwrap().exists()) {
-        let to = to.unwrap();
+        if output.exists() && !cmd.force && fs::read_dir(&output)?.next().is_some() {
+            bail!(
+                "output folder `{}` is not empty, pass `--force` to overwrite it",
+                output.to_string_lossy()
+            );
+        }
 
-        dir::create(to.clone(), false).unwrap();
+        dir::create(&output, true).context("creating output folder")?;
         dir::copy(
-            cmd.directory.clone(),
-            to,
+            &cmd.directory,
+            &output,
             &dir::CopyOptions::new().content_only(true),
         )
-        .unwrap();
+        .context("copying files to output folder")?;
+
+        cmd.directory = output;
+        cmd.no_copy = true;
+    }
+
+    let state_path = cmd.directory.join(STATE_FILE);
+
+    let mut paths = train_files(&cmd.directory, cmd.recursive)?;
+
+    paths.retain(|path| {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let included = cmd
+            .train_filter
+            .as_ref()
+            .is_none_or(|filter| filter.is_match(&file_name));
+        let excluded = cmd
+            .train_filter_exclude
+            .as_ref()
+            .is_some_and(|filter| filter.is_match(&file_name))
+            || cmd
+                .exclude_file
+                .iter()
+                .any(|pattern| pattern.matches(&file_name));
+
+        included && !excluded
+    });
+
+    if let Some(route) = &cmd.route {
+        // Files that cannot be read are kept, so modifying them reports the error.
+        paths.retain(|path| match read_file(path) {
+            Ok((tree, _)) => zusischewe::route(&tree).is_some_and(|name| route.is_match(name)),
+            Err(_) => true,
+        });
+    }
+
+    if let Some(date) = cmd.date {
+        // Like for the route, files that cannot be read are kept.
+        paths.retain(|path| match read_file(path) {
+            Ok((tree, _)) => zusischewe::runs_on(&tree, date).unwrap_or(true),
+            Err(_) => true,
+        });
     }
 
-    let mut rng = rand::thread_rng();
+    if let Some(max_files) = cmd.max_files {
+        paths.truncate(max_files.get());
+    }
 
-    for file in fs::read_dir(&cmd.directory).unwrap() {
-        let path = file.unwrap().path();
+    let mut to = backup_path(&cmd.directory, cmd.backup_dir.as_deref())?;
 
-        if path.extension() != Some(OsStr::new("trn")) {
-            continue;
+    if cmd.stack && to.exists() {
+        to = stack_path(&to, stack_depth(&to) + 1);
+    }
+
+    let mtimes = match cmd.preserve_mtime {
+        true => file_mtimes(&cmd.directory).context("reading modification times")?,
+        false => Vec::new(),
+    };
+
+    let compressed = compressed_backup_path(&to);
+
+    let seed = cmd.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let run = Run {
+        time: chrono::Local::now().format(TIME_FORMAT).to_string(),
+        seed,
+        arguments: std::env::args().skip(1).collect(),
+    };
+    let mut manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        run: run.clone(),
+        parameters: serde_json::to_value(&cmd.params)?,
+        sparse_files: cmd
+            .sparse_backup
+            .then(|| sparse_files(&cmd.directory, &paths))
+            .transpose()?,
+    };
+
+    // An existing `_zsw` folder holds the original files of an earlier run, so it is kept.
+    let reuse_backup = cmd.backup_dir.is_none() && (to.exists() || compressed.exists());
+
+    // Reusing it would restore only the files of the earlier run.
+    if reuse_backup && is_sparse_backup(&to) {
+        bail!(
+            "backup folder `{}` is a sparse backup of an earlier run, reset or remove it before modifying again",
+            to.to_string_lossy()
+        );
+    }
+
+    if cmd.backup_compress && !(cmd.no_copy || reuse_backup) {
+        if compressed.exists() && !cmd.force {
+            bail!(
+                "backup archive `{}` already exists, pass `--force` to overwrite it",
+                compressed.to_string_lossy()
+            );
+        }
+
+        pack_tar_zst(&cmd.directory, &compressed, &manifest)
+            .context("compressing files to backup archive")?;
+    } else if let (Some(files), false) = (&manifest.sparse_files, reuse_backup) {
+        if to.exists() && !cmd.force && fs::read_dir(&to)?.next().is_some() {
+            bail!(
+                "backup folder `{}` is not empty, pass `--force` to overwrite it",
+                to.to_string_lossy()
+            );
         }
 
-        let _ = modify_file(&path, &cmd, &mut rng).inspect_err(|err| {
-            eprintln!("Failed file modification, path: {}", path.to_string_lossy());
+        dir::create(&to, true).context("creating backup folder")?;
 
-            eprintln!("| reason: {}", err.root_cause());
+        for file in files {
+            let original = cmd.directory.join(file);
 
-            for context in err.chain().rev().skip(1) {
-                eprintln!("| when: {context}");
+            if !original.exists() {
+                continue;
             }
-        });
-    }
-}
 
-fn reset(cmd: Reset) {
-    let zsw_dir = copy_name(&cmd.directory).unwrap();
+            let copy = to.join(file);
+
+            if let Some(parent) = copy.parent() {
+                fs::create_dir_all(parent).context("creating backup folder")?;
+            }
 
-    if !zsw_dir.exists() {
-        eprintln!("`_zsw` folder does not exist");
-        return;
+            fs::copy(&original, &copy).with_context(|| {
+                format!(
+                    "copying file to backup folder, path: {}",
+                    original.to_string_lossy()
+                )
+            })?;
+
+            if let Some((_, mtime)) = mtimes.iter().find(|(path, _)| *path == original) {
+                filetime::set_file_mtime(&copy, *mtime).with_context(|| {
+                    format!(
+                        "restoring modification time, path: {}",
+                        copy.to_string_lossy()
+                    )
+                })?;
+            }
+        }
+    } else if !(cmd.no_copy || reuse_backup) {
+        copy_to_backup(&cmd.directory, &to, cmd.force)?;
+
+        for (path, mtime) in &mtimes {
+            let copy = to.join(path.strip_prefix(&cmd.directory)?);
+            filetime::set_file_mtime(&copy, *mtime).with_context(|| {
+                format!(
+                    "restoring modification time, path: {}",
+                    copy.to_string_lossy()
+                )
+            })?;
+        }
     }
 
-    dir::create(cmd.directory.clone(), true).unwrap();
-    dir::move_dir(
-        zsw_dir,
-        cmd.directory,
-        &dir::CopyOptions::new().content_only(true),
-    )
-    .unwrap();
-}
+    // A reused backup folder describes this run from now on, as reset undoes it as well.
+    if !cmd.no_copy && to.is_dir() {
+        manifest
+            .write(&to.join(MANIFEST_FILE))
+            .context("writing manifest to backup folder")?;
+    }
 
-fn main() {
-    let cli = Cli::parse();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cmd.jobs.map_or(0, NonZeroUsize::get))
+        .build()
+        .context("creating thread pool")?;
+
+    // Shown on terminals only, as it would clutter redirected output.
+    let progress = match matches.get_flag("quiet") || !io::stderr().is_terminal() {
+        true => ProgressBar::hidden(),
+        false => PROGRESS.add(ProgressBar::new(paths.len() as u64)),
+    };
+    progress.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} {wide_msg}")
+            .expect("progress bar template is valid"),
+    );
+
+    // The first error with fail fast, after which the remaining files are passed over.
+    let first_error: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let stopped = AtomicBool::new(false);
+
+    let mut reports: Vec<FileReport> = pool.install(|| {
+        paths
+            .par_iter()
+            .filter_map(|path| {
+                if stopped.load(Ordering::Relaxed) {
+                    return None;
+                }
+
+                log::info!("Modifying {}", path.to_string_lossy());
+                progress.set_message(
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+
+                let report = modify_file(path, &cmd.params, seed);
+                progress.inc(1);
+
+                if cmd.fail_fast {
+                    return report
+                        .map_err(|err| {
+                            let mut first_error = first_error.lock().unwrap();
+
+                            if !stopped.swap(true, Ordering::Relaxed) {
+                                *first_error = Some(anyhow::Error::new(err).context(format!(
+                                    "Failed file modification, path: {}",
+                                    path.to_string_lossy()
+                                )));
+                            }
+                        })
+                        .ok();
+                }
+
+                report
+                    .inspect_err(|err| {
+                        log::error!(
+                            "{}",
+                            error_chain(
+                                &format!(
+                                    "Failed file modification, path: {}",
+                                    path.to_string_lossy()
+                                ),
+                                err,
+                            )
+                        );
+                    })
+                    .ok()
+            })
+            .collect()
+    });
+
+    progress.finish_and_clear();
+
+    // The files modified before the error stay modified, so the run is recorded like a finished one.
+    if let Some(err) = first_error.into_inner().unwrap() {
+        if !cmd.params.dry_run {
+            finish_run(&mtimes, state, run, &state_path, &to, &compressed)?;
+        }
+
+        return Err(err);
+    }
+
+    let unknown_tags: BTreeSet<&str> = reports
+        .iter()
+        .flat_map(|report| &report.unknown_tags)
+        .map(String::as_str)
+        .collect();
+
+    let cancelled: Vec<_> = reports
+        .iter()
+        .filter(|report| report.cancelled)
+        .map(|report| {
+            report
+                .file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        })
+        .collect();
+
+    if !cancelled.is_empty() {
+        log::warn!(
+            "Cancelled {} trains, moved to `{CANCELLED_FOLDER}`: {}",
+            cancelled.len(),
+            cancelled.join(", ")
+        );
+    }
+
+    let multiplier_skipped: Vec<_> = reports
+        .iter()
+        .filter(|report| report.multiplier_skipped)
+        .map(|report| {
+            report
+                .file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        })
+        .collect();
+
+    let consist_missing: Vec<_> = reports
+        .iter()
+        .filter(|report| report.consist_missing)
+        .map(|report| {
+            report
+                .file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        })
+        .collect();
+
+    if !consist_missing.is_empty() {
+        log::warn!(
+            "Did not modify the acceleration of {} trains without consist (`FahrzeugVarianten`), they were delayed as usual: {}",
+            consist_missing.len(),
+            consist_missing.join(", ")
+        );
+    }
+
+    if !multiplier_skipped.is_empty() {
+        log::warn!(
+            "Did not modify the acceleration of {} trains, as it is unclear whether they have a locomotive: {}",
+            multiplier_skipped.len(),
+            multiplier_skipped.join(", ")
+        );
+    }
+
+    if !unknown_tags.is_empty() {
+        log::warn!(
+            "Unknown tags in consists, treated as no locomotive, pass `--strict` to fail instead: {}",
+            unknown_tags.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    if let Some(correlation) = cmd.delay_correlation {
+        correlate_delays(&mut reports, correlation, &cmd.params).context("correlating delays")?;
+    }
+
+    if let Some(headway) = cmd.enforce_headway {
+        enforce_headway(&mut reports, headway, &cmd.params).context("enforcing headway")?;
+    }
+
+    // The candidates were backed up before knowing which of them modify would change.
+    if let (Some(files), false) = (&mut manifest.sparse_files, reuse_backup) {
+        prune_sparse_backup(&to, &cmd.directory, files, &reports)
+            .context("removing unchanged files from backup folder")?;
+        manifest
+            .write(&to.join(MANIFEST_FILE))
+            .context("writing manifest to backup folder")?;
+    }
+
+    // A summary leaves the folder as it is, so it counts as not modified.
+    if !cmd.params.dry_run {
+        finish_run(&mtimes, state, run, &state_path, &to, &compressed)?;
+    }
+
+    if cmd.stats {
+        let mut stats = Stats::default();
+
+        for report in &reports {
+            stats.add(report);
+        }
+
+        stats.print();
+    }
+
+    if let Some(report_path) = &cmd.report {
+        write_report(report_path, &reports, cmd.report_format)
+            .with_context(|| format!("writing report, path: {}", report_path.to_string_lossy()))?;
+    }
+
+    if let Some(csv_path) = &cmd.csv {
+        write_csv(csv_path, &reports)
+            .with_context(|| format!("writing CSV, path: {}", csv_path.to_string_lossy()))?;
+    }
+
+    // Errors of the failed files were already printed while modifying.
+    let failed = paths.len() - reports.len();
+
+    if failed != 0 {
+        bail!("{failed} of {} files failed", paths.len());
+    }
+
+    Ok(())
+}
+
+/// Progress bars currently shown. Logs are printed while they are hidden, so the two do not overwrite each other.
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Writes logs to stderr around the progress bars.
+struct LogWriter;
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        PROGRESS.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Restores the preserved `mtimes` of the modified files and adds `run` to the `state` at `state_path`, after modifying. The modification time of `backup`, or of its `compressed` archive, is updated.
+fn finish_run(
+    mtimes: &[(PathBuf, FileTime)],
+    mut state: State,
+    run: Run,
+    state_path: &Path,
+    backup: &Path,
+    compressed: &Path,
+) -> anyhow::Result<()> {
+    // Cancelled trains were moved, which keeps their modification time anyway.
+    for (path, mtime) in mtimes {
+        if path.exists() {
+            filetime::set_file_mtime(path, *mtime).with_context(|| {
+                format!(
+                    "restoring modification time, path: {}",
+                    path.to_string_lossy()
+                )
+            })?;
+        }
+    }
+
+    state.runs.push(run);
+    state
+        .write(state_path)
+        .with_context(|| format!("writing state, path: {}", state_path.to_string_lossy()))?;
+
+    // Reset compares against this to find files that were changed after this run.
+    if backup.exists() {
+        filetime::set_file_mtime(backup, FileTime::now())
+            .context("updating modification time of backup folder")?;
+    } else if compressed.exists() {
+        filetime::set_file_mtime(compressed, FileTime::now())
+            .context("updating modification time of backup archive")?;
+    }
+
+    Ok(())
+}
+
+/// File inside the modified folder recording the runs of modify on it.
+const STATE_FILE: &str = ".zsw-state.json";
+
+/// Contents of the state file. Kept in the backups, so reset restores the state of the restored files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    runs: Vec<Run>,
+}
+
+/// A run of modify on a folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Run {
+    time: String,
+    seed: u64,
+    /// Command line arguments, without the name of the program.
+    arguments: Vec<String>,
+}
+
+impl State {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+
+        Ok(())
+    }
+}
+
+/// File inside each backup, describing the run it was made for. Reset prints it, backups of older versions have none.
+const MANIFEST_FILE: &str = ".zsw-manifest.json";
+
+/// Contents of the manifest file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(flatten)]
+    run: Run,
+    /// All parameters, including those of the preset and the config file.
+    parameters: serde_json::Value,
+    /// The files a sparse backup is of, relative to the modified folder, including those that did not exist yet. `None` for backups of the whole folder.
+    #[serde(default)]
+    sparse_files: Option<Vec<PathBuf>>,
+}
+
+impl Manifest {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+
+        Ok(())
+    }
+}
+
+/// The files a sparse backup of `paths`, the trains inside `directory` to modify, is of: the trains, their timetables and the state file.
+fn sparse_files(directory: &Path, paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![PathBuf::from(STATE_FILE)];
+
+    for path in paths {
+        let file = path.strip_prefix(directory)?;
+
+        files.push(file.to_path_buf());
+        files.push(file.with_extension("timetable.xml"));
+    }
+
+    Ok(files)
+}
+
+/// Removes the trains of `reports` that were neither changed nor cancelled, and their timetables, from the sparse backup `backup` of `directory` and from its `files`.
+fn prune_sparse_backup(
+    backup: &Path,
+    directory: &Path,
+    files: &mut Vec<PathBuf>,
+    reports: &[FileReport],
+) -> anyhow::Result<()> {
+    for report in reports {
+        if report.changed || report.cancelled {
+            continue;
+        }
+
+        let file = report.file.strip_prefix(directory)?;
+
+        for file in [file.to_path_buf(), file.with_extension("timetable.xml")] {
+            let copy = backup.join(&file);
+
+            if copy.exists() {
+                fs::remove_file(&copy)
+                    .with_context(|| format!("removing `{}`", copy.to_string_lossy()))?;
+            }
+
+            files.retain(|listed| *listed != file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the manifest of backup folder `backup` lists the files of a sparse backup.
+fn is_sparse_backup(backup: &Path) -> bool {
+    Manifest::read(&backup.join(MANIFEST_FILE))
+        .is_ok_and(|manifest| manifest.sparse_files.is_some())
+}
+
+/// Entry and departure delays over all modified trains.
+#[derive(Debug, Default)]
+struct Stats {
+    trains: usize,
+    delayed: usize,
+    mean_entry_delay_seconds: f64,
+    entry_delays_seconds: Vec<i64>,
+    departures_delay_seconds: i64,
+    cancelled: usize,
+    skipped: usize,
+    multiplier_skipped: usize,
+    consist_missing: usize,
+}
+
+impl Stats {
+    fn add(&mut self, report: &FileReport) {
+        if report.cancelled {
+            self.cancelled += 1;
+            return;
+        }
+
+        if report.skipped {
+            self.skipped += 1;
+            return;
+        }
+
+        self.multiplier_skipped += report.multiplier_skipped as usize;
+        self.consist_missing += report.consist_missing as usize;
+
+        let entry_delay = report.entry_delay_seconds;
+
+        self.trains += 1;
+        self.mean_entry_delay_seconds +=
+            (entry_delay as f64 - self.mean_entry_delay_seconds) / self.trains as f64;
+        self.entry_delays_seconds.push(entry_delay);
+        self.departures_delay_seconds += report.departures_delay_seconds;
+
+        if entry_delay > 0 {
+            self.delayed += 1;
+        }
+    }
+
+    fn print(mut self) {
+        self.entry_delays_seconds.sort_unstable();
+
+        let median = match self.entry_delays_seconds.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => {
+                (self.entry_delays_seconds[len / 2 - 1] + self.entry_delays_seconds[len / 2]) as f64
+                    / 2.0
+            }
+            len => self.entry_delays_seconds[len / 2] as f64,
+        };
+        let p95 = match self.entry_delays_seconds.len() {
+            0 => 0,
+            len => self.entry_delays_seconds[((len - 1) as f64 * 0.95).round() as usize],
+        };
+        let max = self.entry_delays_seconds.last().copied().unwrap_or(0);
+
+        let cancelled = match self.cancelled {
+            0 => String::new(),
+            cancelled => format!(", {cancelled} trains cancelled"),
+        };
+        let skipped = match self.skipped {
+            0 => String::new(),
+            skipped => format!(", {skipped} trains skipped"),
+        };
+        let multiplier_skipped = match self.multiplier_skipped {
+            0 => String::new(),
+            skipped => format!(", acceleration of {skipped} trains not modified"),
+        };
+        let consist_missing = match self.consist_missing {
+            0 => String::new(),
+            missing => format!(", {missing} trains without consist"),
+        };
+
+        eprintln!(
+            "Delayed entry of {} of {} trains, entry delay mean {:.1} min, median {:.1} min, p95 {:.1} min, max {:.1} min, departures delayed by {:.1} min in total{cancelled}{skipped}{multiplier_skipped}{consist_missing}",
+            self.delayed,
+            self.trains,
+            self.mean_entry_delay_seconds / 60.0,
+            median / 60.0,
+            p95 as f64 / 60.0,
+            max as f64 / 60.0,
+            self.departures_delay_seconds as f64 / 60.0,
+        );
+    }
+}
+
+fn write_report(path: &Path, reports: &[FileReport], format: ReportFormat) -> anyhow::Result<()> {
+    match format {
+        ReportFormat::Human => fs::write(path, report_table(reports))?,
+        ReportFormat::Json => serde_json::to_writer_pretty(File::create(path)?, reports)?,
+        ReportFormat::Csv => fs::write(path, report_csv(reports))?,
+    }
+
+    Ok(())
+}
+
+/// What happened to the train of `report` instead of or besides being modified, for the report.
+fn report_note(report: &FileReport) -> &'static str {
+    if report.cancelled {
+        "cancelled"
+    } else if report.skipped {
+        "skipped"
+    } else if report.consist_missing {
+        "no consist"
+    } else if report.multiplier_skipped {
+        "acceleration not modified"
+    } else {
+        ""
+    }
+}
+
+/// The report as a table with a row for each train.
+fn report_table(reports: &[FileReport]) -> String {
+    let width = reports
+        .iter()
+        .map(|report| report.train.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Train".len());
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<width$}  {:>10}  {:>11}  {:>10}  {:>15}  Note",
+        "Train", "Multiplier", "Entry delay", "Departures", "Departure delay",
+    );
+
+    for report in reports {
+        let row = format!(
+            "{:<width$}  {:>10.3}  {:>7.1} min  {:>10}  {:>11.1} min  {}",
+            report.train,
+            report.multiplier,
+            report.entry_delay_seconds as f64 / 60.0,
+            report.departures_delayed,
+            report.departures_delay_seconds as f64 / 60.0,
+            report_note(report),
+        );
+        let _ = writeln!(out, "{}", row.trim_end());
+    }
+
+    out
+}
+
+/// The report as CSV with a row for each file.
+fn report_csv(reports: &[FileReport]) -> String {
+    let mut out = String::from(
+        "file,train,has_locomotive,multiplier,entry_delay_seconds,departures_delayed,departures_delay_seconds,note\n",
+    );
+
+    for report in reports {
+        let fields = [
+            report.file.to_string_lossy().into_owned(),
+            report.train.clone(),
+            report
+                .has_locomotive
+                .map_or(String::new(), |has_locomotive| has_locomotive.to_string()),
+            report.multiplier.to_string(),
+            report.entry_delay_seconds.to_string(),
+            report.departures_delayed.to_string(),
+            report.departures_delay_seconds.to_string(),
+            report_note(report).to_owned(),
+        ]
+        .map(|field| csv_field(&field));
+
+        let _ = writeln!(out, "{}", fields.join(","));
+    }
+
+    out
+}
+
+fn write_csv(path: &Path, reports: &[FileReport]) -> anyhow::Result<()> {
+    let mut out =
+        String::from("train,station,original_arrival,arrival,original_departure,departure\n");
+
+    for report in reports {
+        for change in &report.stop_changes {
+            let fields = [
+                Some(&report.train),
+                change.station.as_ref(),
+                change.original_arrival.as_ref(),
+                change.arrival.as_ref(),
+                change.original_departure.as_ref(),
+                change.departure.as_ref(),
+            ]
+            .map(|field| csv_field(field.map_or("", String::as_str)));
+
+            let _ = writeln!(out, "{}", fields.join(","));
+        }
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+fn verify(cmd: Verify) -> anyhow::Result<()> {
+    let paths = train_files(&cmd.directory, false)?;
+
+    let mut failed = 0;
+
+    for path in &paths {
+        log::info!("Verifying {}", path.to_string_lossy());
+
+        if let Err(err) = verify_file(
+            path,
+            cmd.loc_pattern.as_ref(),
+            &cmd.locomotive_substring,
+            cmd.strict,
+        ) {
+            log::error!(
+                "{}",
+                error_chain(
+                    &format!("Failed verification, path: {}", path.to_string_lossy()),
+                    &err,
+                )
+            );
+            failed += 1;
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} files failed verification", paths.len());
+    }
+
+    eprintln!("All {} files passed verification", paths.len());
+
+    Ok(())
+}
+
+/// Quotes `field` for CSV if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
+
+fn list_stations(cmd: ListStations) -> anyhow::Result<()> {
+    let paths = train_files(&cmd.directory, false)?;
+
+    let mut stations: BTreeMap<String, usize> = BTreeMap::new();
+    let mut failed = 0;
+
+    for path in &paths {
+        match read_file(path).and_then(|(tree, _)| zusischewe::stations(&tree)) {
+            Ok(names) => {
+                for name in names {
+                    *stations.entry(name).or_default() += 1;
+                }
+            }
+            Err(err) => {
+                log::error!(
+                    "{}",
+                    error_chain(
+                        &format!("Failed reading stations, path: {}", path.to_string_lossy()),
+                        &err,
+                    )
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} files could not be read", paths.len());
+    }
+
+    let mut out = String::new();
+
+    match cmd.csv {
+        true => {
+            out.push_str("station,stops\n");
+
+            for (station, stops) in &stations {
+                let _ = writeln!(out, "{},{stops}", csv_field(station));
+            }
+        }
+        false => {
+            for (station, stops) in &stations {
+                let _ = writeln!(out, "{stops:>6}  {station}");
+            }
+        }
+    }
+
+    print!("{out}");
+
+    Ok(())
+}
+
+/// Width of the longest bar of the histogram of preview distribution.
+const HISTOGRAM_WIDTH: usize = 50;
+/// Number of bars of the histogram of preview distribution.
+const HISTOGRAM_BINS: usize = 20;
+
+fn preview_distribution(cmd: PreviewDistribution) -> anyhow::Result<()> {
+    let mut rng = match cmd.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let mut minutes = (0..cmd.samples.get())
+        .map(|_| entry_delay_minutes(&cmd.params, None, &mut rng))
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    minutes.sort_by(f32::total_cmp);
+
+    let quantile = |q: f32| minutes[((minutes.len() - 1) as f32 * q).round() as usize];
+    let (min, max) = (minutes[0], minutes[minutes.len() - 1]);
+    let mean = minutes.iter().map(|&minutes| minutes as f64).sum::<f64>() / minutes.len() as f64;
+
+    let mut out = String::new();
+
+    // All samples in a single bin if they are equal, like when no delay type applies.
+    let bin_width = (max - min) / HISTOGRAM_BINS as f32;
+    let mut bins = [0usize; HISTOGRAM_BINS];
+
+    for &sample in &minutes {
+        let bin = match bin_width > 0.0 {
+            true => (((sample - min) / bin_width) as usize).min(HISTOGRAM_BINS - 1),
+            false => 0,
+        };
+        bins[bin] += 1;
+    }
+
+    let largest = bins.iter().copied().max().unwrap_or(0).max(1);
+    let used_bins = if bin_width > 0.0 { HISTOGRAM_BINS } else { 1 };
+
+    for (index, &count) in bins[..used_bins].iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{:>8.1} min {count:>8}  {}",
+            min + index as f32 * bin_width,
+            "#".repeat(count * HISTOGRAM_WIDTH / largest)
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "\n{} samples, mean {mean:.1} min, median {:.1} min, p95 {:.1} min, min {min:.1} min, max {max:.1} min",
+        minutes.len(),
+        quantile(0.5),
+        quantile(0.95),
+    );
+
+    print!("{out}");
+
+    Ok(())
+}
+
+fn info(cmd: Info) -> anyhow::Result<()> {
+    let paths = train_files(&cmd.directory, false)?;
+
+    let mut types: BTreeMap<String, usize> = BTreeMap::new();
+    let mut stations: BTreeSet<String> = BTreeSet::new();
+    let mut with_locomotive = 0;
+    let mut without_consist = 0;
+    let mut unknown_tags = Vec::new();
+    let mut first_arrival: Option<chrono::NaiveDateTime> = None;
+    let mut last_arrival: Option<chrono::NaiveDateTime> = None;
+    let mut failed = 0;
+
+    for path in &paths {
+        let result = read_file(path).and_then(|(tree, _)| {
+            let has_locomotive = train_has_locomotive(
+                &tree,
+                cmd.loc_pattern.as_ref(),
+                &cmd.locomotive_substring,
+                Some(&mut unknown_tags),
+            )?;
+            let arrivals = arrival_times(&tree)?;
+            let names = zusischewe::stations(&tree)?;
+
+            let gattung = tree
+                .get_child("Zug")
+                .and_then(|zug| zug.attributes.get("Gattung"))
+                .map_or("", |gattung| gattung.trim())
+                .to_owned();
+
+            Ok((has_locomotive, arrivals, names, gattung))
+        });
+
+        match result {
+            Ok((has_locomotive, arrivals, names, gattung)) => {
+                *types.entry(gattung).or_default() += 1;
+                match has_locomotive {
+                    Some(has_locomotive) => with_locomotive += has_locomotive as usize,
+                    None => without_consist += 1,
+                }
+                stations.extend(names.into_iter().map(|name| name.trim().to_owned()));
+
+                for arrival in arrivals {
+                    first_arrival = Some(first_arrival.map_or(arrival, |first| first.min(arrival)));
+                    last_arrival = Some(last_arrival.map_or(arrival, |last| last.max(arrival)));
+                }
+            }
+            Err(err) => {
+                log::error!(
+                    "{}",
+                    error_chain(
+                        &format!("Failed reading train, path: {}", path.to_string_lossy()),
+                        &err,
+                    )
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} files could not be read", paths.len());
+    }
+
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Trains: {}", paths.len());
+
+    for (gattung, count) in &types {
+        let gattung = if gattung.is_empty() {
+            "(no type)"
+        } else {
+            gattung
+        };
+        let _ = writeln!(out, "{count:>6}  {gattung}");
+    }
+
+    let _ = writeln!(out, "With locomotive: {with_locomotive} of {}", paths.len());
+
+    // Their acceleration is left as it is by modify.
+    if without_consist != 0 {
+        let _ = writeln!(out, "Without consist: {without_consist}");
+    }
+
+    if let (Some(first), Some(last)) = (first_arrival, last_arrival) {
+        let _ = writeln!(
+            out,
+            "Arrivals: {} to {}",
+            first.format(TIME_FORMAT),
+            last.format(TIME_FORMAT)
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "Stations ({}): {}",
+        stations.len(),
+        stations.into_iter().collect::<Vec<_>>().join(", ")
+    );
+
+    print!("{out}");
+
+    unknown_tags.sort();
+    unknown_tags.dedup();
+
+    if !unknown_tags.is_empty() {
+        log::warn!(
+            "Unknown tags in consists, treated as no locomotive: {}",
+            unknown_tags.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Modification times of the files in `dir` and its subfolders.
+fn file_mtimes(dir: &Path) -> anyhow::Result<Vec<(PathBuf, FileTime)>> {
+    let mut mtimes = Vec::new();
+
+    for file in WalkDir::new(dir).min_depth(1) {
+        let file = file?;
+        let metadata = file.metadata()?;
+
+        if metadata.is_file() {
+            mtimes.push((
+                file.into_path(),
+                FileTime::from_last_modification_time(&metadata),
+            ));
+        }
+    }
+
+    Ok(mtimes)
+}
+
+/// Files inside `dir` and its subfolders that were modified after `time`.
+fn files_modified_after(dir: &Path, time: SystemTime) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for file in WalkDir::new(dir).min_depth(1) {
+        let file = file?;
+        let metadata = file.metadata()?;
+
+        if metadata.is_file() && metadata.modified()? > time {
+            files.push(file.into_path());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether `path` is a `.zip` archive, which modify and reset extract and repackage instead of working on a folder.
+fn is_zip(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// The backup of archive `path` kept next to it, like `Scenario_zsw.zip` for `Scenario.zip`.
+fn zip_backup_path(path: &Path) -> anyhow::Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .with_context(|| format!("`{}` has no file name", path.to_string_lossy()))?;
+
+    let mut file_name = stem.to_os_string();
+    file_name.push("_zsw.zip");
+    Ok(path.with_file_name(file_name))
+}
+
+/// Extracts archive `path` into `dir`. Fails on entries that would be extracted outside of `dir`.
+fn extract_zip(path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+
+        // Names like `../x` or absolute paths would write outside of the folder.
+        let Some(name) = entry.enclosed_name() else {
+            bail!("entry `{}` points outside of the archive", entry.name()?);
+        };
+        let out = dir.join(name);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&out)?;
+            continue;
+        }
+
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        std::io::copy(&mut entry, &mut File::create(&out)?)?;
+    }
+
+    Ok(())
+}
+
+/// Packs the files inside `dir` into archive `path`, replacing it.
+fn pack_zip(dir: &Path, path: &Path) -> anyhow::Result<()> {
+    let mut archive = zip::ZipWriter::new(File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default();
+
+    for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        // Archives always use `/`, also on Windows.
+        let name = entry
+            .path()
+            .strip_prefix(dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if entry.file_type().is_dir() {
+            archive.add_directory(name, options)?;
+        } else {
+            archive.start_file(name, options)?;
+            std::io::copy(&mut File::open(entry.path())?, &mut archive)?;
+        }
+    }
+
+    archive.finish()?;
+
+    Ok(())
+}
+
+/// The compressed backup of backup folder `backup`, like `Scenario_zsw.tar.zst` for `Scenario_zsw`.
+fn compressed_backup_path(backup: &Path) -> PathBuf {
+    let mut path = backup.as_os_str().to_os_string();
+    path.push(".tar.zst");
+    PathBuf::from(path)
+}
+
+/// Packs the files inside `dir` and `manifest` into the `.tar.zst` archive `path`, replacing it. Modification times are kept.
+fn pack_tar_zst(dir: &Path, path: &Path, manifest: &Manifest) -> anyhow::Result<()> {
+    let mut archive = tar::Builder::new(zstd::Encoder::new(File::create(path)?, 0)?);
+    archive.append_dir_all(".", dir)?;
+
+    let manifest = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(FileTime::now().unix_seconds().max(0) as u64);
+    archive.append_data(&mut header, MANIFEST_FILE, manifest.as_slice())?;
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// Resets from the compressed backup `compressed` of backup folder `backup`, by extracting it into the backup folder and resetting from that. The archive is removed once the reset succeeded.
+fn reset_compressed(cmd: Reset, backup: &Path, compressed: &Path) -> anyhow::Result<()> {
+    // Modify updates the time of the archive like that of the backup folder.
+    let time = FileTime::from_last_modification_time(
+        &fs::metadata(compressed).context("reading modification time of backup archive")?,
+    );
+
+    tar::Archive::new(zstd::Decoder::new(File::open(compressed)?)?)
+        .unpack(backup)
+        .context("extracting backup archive")?;
+    filetime::set_file_mtime(backup, time)
+        .context("updating modification time of backup folder")?;
+
+    let keep = cmd.keep_backup;
+    let result = reset(cmd);
+
+    if backup.exists() {
+        fs::remove_dir_all(backup).context("removing extracted backup folder")?;
+    }
+
+    if result.is_ok() && !keep {
+        fs::remove_file(compressed).context("removing backup archive")?;
+    }
+
+    result
+}
+
+/// Modifies the trains inside the `.zip` archive of `cmd`, by extracting it into a temporary folder and packing it again. The original archive is kept next to it, see `zip_backup_path`.
+fn modify_zip(mut cmd: Modify, matches: &ArgMatches) -> anyhow::Result<()> {
+    if cmd.stack || cmd.backup_dir.is_some() || cmd.output.is_some() || cmd.summary_only {
+        bail!("backup stacks, backup folders, output folders and summaries are not supported for archives");
+    }
+
+    let archive = cmd.directory.clone();
+    let backup = zip_backup_path(&archive)?;
+
+    // Like the `_zsw` folder, an existing backup holds the original archive of an earlier run.
+    if !cmd.no_copy && !backup.exists() {
+        fs::copy(&archive, &backup).context("copying archive to backup")?;
+    }
+
+    let temp_dir = std::env::temp_dir().join(format!("zusischewe-{}", process::id()));
+
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir).context("removing old temporary folder")?;
+    }
+
+    let result = extract_zip(&archive, &temp_dir)
+        .context("extracting archive")
+        .and_then(|()| {
+            cmd.directory = temp_dir.clone();
+            cmd.no_copy = true;
+
+            // Files that were modified before a failure are packed anyway, as they would be in a folder.
+            let result = modify(cmd, matches);
+
+            pack_zip(&temp_dir, &archive).context("packing archive")?;
+
+            result
+        });
+
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    result
+}
+
+/// Prints the statistics of modifying the folder of `cmd`, without changing any files, see `ModifyParams::dry_run`.
+fn summarize(mut cmd: Modify, matches: &ArgMatches) -> anyhow::Result<()> {
+    cmd.params.dry_run = true;
+    cmd.no_copy = true;
+    cmd.stats = true;
+
+    modify(cmd, matches)
+}
+
+/// Restores the `.zip` archive of `cmd` from its backup next to it.
+fn reset_zip(cmd: Reset) -> anyhow::Result<()> {
+    if cmd.backup_dir.is_some()
+        || cmd.snapshot.is_some()
+        || cmd.steps.is_some_and(|steps| steps.get() > 1)
+    {
+        bail!("backup stacks, backup folders and snapshots are not supported for archives");
+    }
+
+    let backup = zip_backup_path(&cmd.directory)?;
+
+    if !backup.exists() {
+        bail!(
+            "backup archive `{}` does not exist",
+            backup.to_string_lossy()
+        );
+    }
+
+    if cmd.dry_run {
+        println!(
+            "Would replace `{}` with `{}`",
+            cmd.directory.to_string_lossy(),
+            backup.to_string_lossy()
+        );
+        return Ok(());
+    }
+
+    match cmd.keep_backup {
+        true => {
+            fs::copy(&backup, &cmd.directory).context("copying archive back from backup")?;
+        }
+        false => fs::rename(&backup, &cmd.directory).context("moving archive back from backup")?,
+    }
+
+    Ok(())
+}
+
+/// Paths of the files inside `dir` and its subfolders, relative to `dir`.
+fn relative_files(dir: &Path) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+
+    for file in WalkDir::new(dir).min_depth(1) {
+        let file = file?;
+
+        if file.file_type().is_file() {
+            files.insert(file.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+/// The files of backup folder `zsw_dir`, with whether each differs from that in `directory` or is missing there.
+fn backed_up_files(directory: &Path, zsw_dir: &Path) -> anyhow::Result<BTreeMap<PathBuf, bool>> {
+    let mut files = BTreeMap::new();
+
+    for file in relative_files(zsw_dir).context("reading backup folder")? {
+        let live = directory.join(&file);
+        let differs = !live.exists() || fs::read(&live)? != fs::read(zsw_dir.join(&file))?;
+        files.insert(file, differs);
+    }
+
+    files.remove(Path::new(MANIFEST_FILE));
+
+    Ok(files)
+}
+
+/// Like `backed_up_files`, for the compressed backup `compressed`, whose entries are read without extracting it.
+fn compressed_files(
+    directory: &Path,
+    compressed: &Path,
+) -> anyhow::Result<BTreeMap<PathBuf, bool>> {
+    let mut files = BTreeMap::new();
+    let mut archive = tar::Archive::new(zstd::Decoder::new(File::open(compressed)?)?);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        // The files were packed below `.`, see `pack_tar_zst`.
+        let file: PathBuf = entry
+            .path()?
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        let live = directory.join(&file);
+
+        let differs = match live.exists() {
+            true => {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                fs::read(&live)? != contents
+            }
+            false => true,
+        };
+        files.insert(file, differs);
+    }
+
+    files.remove(Path::new(MANIFEST_FILE));
+
+    Ok(files)
+}
+
+/// Prints what reset would do restoring `directory` from the `backed_up` files, see `backed_up_files`, and the backup folders it would remove.
+fn print_reset_plan(
+    directory: &Path,
+    backed_up: &BTreeMap<PathBuf, bool>,
+    removed: &[PathBuf],
+) -> anyhow::Result<()> {
+    let live = relative_files(directory).context("reading folder")?;
+
+    let mut out = String::new();
+    let (mut deleted, mut restored, mut overwritten, mut unchanged) = (0, 0, 0, 0);
+
+    for file in live.iter().filter(|file| !backed_up.contains_key(*file)) {
+        let _ = writeln!(out, "Would delete:    {}", file.to_string_lossy());
+        deleted += 1;
+    }
+
+    for (file, differs) in backed_up {
+        if !live.contains(file) {
+            let _ = writeln!(out, "Would restore:   {}", file.to_string_lossy());
+            restored += 1;
+        } else if *differs {
+            let _ = writeln!(out, "Would overwrite: {}", file.to_string_lossy());
+            overwritten += 1;
+        } else {
+            unchanged += 1;
+        }
+    }
+
+    for folder in removed {
+        let _ = writeln!(
+            out,
+            "Would remove backup folder: {}",
+            folder.to_string_lossy()
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "{deleted} files would be deleted, {restored} restored and {overwritten} overwritten, {unchanged} are unchanged"
+    );
+
+    print!("{out}");
+
+    Ok(())
+}
+
+/// Whether `dir` or any of its subfolders contains a `.trn` file.
+fn contains_train_files(dir: &Path) -> bool {
+    WalkDir::new(dir).into_iter().flatten().any(|entry| {
+        entry.file_type().is_file() && entry.path().extension() == Some(OsStr::new("trn"))
+    })
+}
+
+/// Backup folder `level` of the stack on top of `backup`, which is level 0.
+fn stack_path(backup: &Path, level: usize) -> PathBuf {
+    match level {
+        0 => backup.to_path_buf(),
+        level => {
+            let mut file_name = backup.file_name().unwrap_or_default().to_os_string();
+            file_name.push(format!(".{level}"));
+            backup.with_file_name(file_name)
+        }
+    }
+}
+
+/// Number of backup folders stacked on top of `backup`.
+fn stack_depth(backup: &Path) -> usize {
+    (1..)
+        .take_while(|&level| stack_path(backup, level).exists())
+        .count()
+}
+
+/// Restores the `files` of the sparse backup `zsw_dir` into the folder of `cmd`, deleting those the backup has no copy of, as they did not exist before modifying. Other files are left as they are.
+fn reset_sparse(cmd: &Reset, zsw_dir: &Path, files: &[PathBuf]) -> anyhow::Result<()> {
+    let (mut deleted, mut restored) = (0, 0);
+
+    for file in files {
+        let live = cmd.directory.join(file);
+        let copy = zsw_dir.join(file);
+        // Cancelled trains were moved out of the way, see `CANCELLED_FOLDER`.
+        let cancelled = live
+            .with_file_name(CANCELLED_FOLDER)
+            .join(file.file_name().unwrap_or_default());
+
+        if cmd.dry_run {
+            if copy.exists() {
+                println!("Would restore:   {}", file.to_string_lossy());
+                restored += 1;
+            } else if live.exists() {
+                println!("Would delete:    {}", file.to_string_lossy());
+                deleted += 1;
+            }
+
+            continue;
+        }
+
+        for path in [&live, &cancelled] {
+            if path.exists() {
+                fs::remove_file(path)
+                    .with_context(|| format!("removing `{}`", path.to_string_lossy()))?;
+            }
+        }
+
+        if copy.exists() {
+            match cmd.keep_backup {
+                true => fs::copy(&copy, &live).map(|_| ()),
+                false => fs::rename(&copy, &live),
+            }
+            .with_context(|| format!("restoring `{}`", file.to_string_lossy()))?;
+        }
+
+        // The folder of cancelled trains is removed once the last of them is restored.
+        if let Some(folder) = cancelled.parent() {
+            if folder
+                .read_dir()
+                .is_ok_and(|mut entries| entries.next().is_none())
+            {
+                fs::remove_dir(folder).context("removing folder of cancelled trains")?;
+            }
+        }
+    }
+
+    if cmd.dry_run {
+        println!("{deleted} files would be deleted and {restored} restored, other files are left as they are");
+        return Ok(());
+    }
+
+    match cmd.keep_backup {
+        true => filetime::set_file_mtime(zsw_dir, FileTime::now())
+            .context("updating modification time of backup folder")?,
+        false => fs::remove_dir_all(zsw_dir).context("removing backup folder")?,
+    }
+
+    Ok(())
+}
+
+fn reset(cmd: Reset) -> anyhow::Result<()> {
+    let backup = match &cmd.snapshot {
+        Some(name) => snapshot_path(&cmd.directory, Some(name))?,
+        None => backup_path(&cmd.directory, cmd.backup_dir.as_deref())?,
+    };
+    let compressed = compressed_backup_path(&backup);
+
+    if !backup.exists() && compressed.exists() {
+        if cmd.dry_run {
+            let backed_up =
+                compressed_files(&cmd.directory, &compressed).context("reading backup archive")?;
+            return print_reset_plan(&cmd.directory, &backed_up, &[]);
+        }
+
+        return reset_compressed(cmd, &backup, &compressed);
+    }
+
+    if !backup.exists() {
+        bail!(
+            "backup folder `{}` does not exist",
+            backup.to_string_lossy()
+        );
+    }
+
+    let depth = stack_depth(&backup);
+    let steps = match cmd.all {
+        true => depth + 1,
+        false => cmd.steps.map_or(1, NonZeroUsize::get),
+    };
+
+    if steps > depth + 1 {
+        bail!(
+            "unable to undo {steps} modifications, the backups only go back {} modifications",
+            depth + 1
+        );
+    }
+
+    let zsw_dir = stack_path(&backup, depth + 1 - steps);
+
+    let manifest_path = zsw_dir.join(MANIFEST_FILE);
+
+    let manifest = match manifest_path.exists() {
+        true => match Manifest::read(&manifest_path) {
+            Ok(manifest) => Some(manifest),
+            Err(err) => {
+                log::warn!("Unable to read manifest of backup: {err:#}");
+                None
+            }
+        },
+        false => None,
+    };
+
+    if let Some(manifest) = &manifest {
+        println!(
+            "Restoring `{}` modified at {} by zusischewe {} with seed {} and arguments: {}",
+            cmd.directory.to_string_lossy(),
+            manifest.run.time,
+            manifest.version,
+            manifest.run.seed,
+            manifest.run.arguments.join(" ")
+        );
+    }
+
+    let sparse_files = manifest.and_then(|manifest| manifest.sparse_files);
+
+    // The folder is emptied before moving the files back, so a backup left empty by a failed run would leave nothing.
+    if sparse_files.is_none() && !contains_train_files(&zsw_dir) {
+        bail!(
+            "backup folder `{}` contains no '.trn' files, refusing to replace the folder with it",
+            zsw_dir.to_string_lossy()
+        );
+    }
+
+    // Before checking for changed files, which a dry run leaves as they are.
+    if cmd.dry_run {
+        if let Some(files) = &sparse_files {
+            return reset_sparse(&cmd, &zsw_dir, files);
+        }
+
+        let backed_up = backed_up_files(&cmd.directory, &zsw_dir)?;
+        let removed: Vec<_> = ((depth + 2 - steps)..=depth)
+            .map(|level| stack_path(&backup, level))
+            .collect();
+
+        return print_reset_plan(&cmd.directory, &backed_up, &removed);
+    }
+
+    if !cmd.force {
+        // Modify updates the time of the newest backup folder at the end of each run.
+        let backup_time = fs::metadata(stack_path(&backup, depth))
+            .and_then(|metadata| metadata.modified())
+            .context("reading modification time of backup folder")?;
+
+        let newer = files_modified_after(&cmd.directory, backup_time)
+            .context("looking for files changed after the last modification")?;
+
+        if !newer.is_empty() {
+            for file in &newer {
+                eprintln!(
+                    "Changed after the last modification: {}",
+                    file.to_string_lossy()
+                );
+            }
+
+            bail!(
+                "{} files were changed after the last modification and would be lost, pass `--force` to reset anyway",
+                newer.len()
+            );
+        }
+    }
+
+    if let Some(files) = sparse_files {
+        return reset_sparse(&cmd, &zsw_dir, &files);
+    }
+
+    dir::create(&cmd.directory, true).context("emptying folder")?;
+
+    match cmd.keep_backup {
+        true => {
+            dir::copy(
+                &zsw_dir,
+                &cmd.directory,
+                &dir::CopyOptions::new().content_only(true),
+            )
+            .context("copying files back from backup folder")?;
+        }
+        false => {
+            dir::move_dir(
+                &zsw_dir,
+                &cmd.directory,
+                &dir::CopyOptions::new().content_only(true),
+            )
+            .context("moving files back from backup folder")?;
+        }
+    }
+
+    let restored_manifest = cmd.directory.join(MANIFEST_FILE);
+
+    if restored_manifest.exists() {
+        fs::remove_file(&restored_manifest).context("removing restored manifest")?;
+    }
+
+    // The newer backups are of modifications that were just undone.
+    for level in (depth + 2 - steps)..=depth {
+        fs::remove_dir_all(stack_path(&backup, level)).context("removing undone backup folders")?;
+    }
+
+    // The restored files are those of the modification now on top of the stack, so they count as unchanged since. A kept backup is on top itself.
+    if cmd.keep_backup {
+        filetime::set_file_mtime(&zsw_dir, FileTime::now())
+            .context("updating modification time of backup folder")?;
+    } else if steps <= depth {
+        filetime::set_file_mtime(stack_path(&backup, depth - steps), FileTime::now())
+            .context("updating modification time of backup folder")?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    env_logger::Builder::new()
+        .filter_level(match (cli.quiet, cli.verbose) {
+            (true, _) => log::LevelFilter::Off,
+            (false, 0) => log::LevelFilter::Warn,
+            (false, 1) => log::LevelFilter::Info,
+            (false, _) => log::LevelFilter::Debug,
+        })
+        // Without level or timestamp, so errors look like they always did.
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .target(env_logger::Target::Pipe(Box::new(LogWriter)))
+        .init();
+
+    let result = match cli.command {
+        Command::Modify(cmd) if cmd.list_presets => {
+            list_presets().map_err(|err| ("Failed listing presets".to_owned(), err))
+        }
+        Command::Modify(cmd) => {
+            let message = format!(
+                "Failed modification, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            let matches = matches
+                .subcommand_matches("modify")
+                .expect("modify command has matches");
+
+            match (is_zip(&cmd.directory), cmd.summary_only) {
+                (true, _) => modify_zip(*cmd, matches),
+                (false, true) => summarize(*cmd, matches),
+                (false, false) => modify(*cmd, matches),
+            }
+            .map_err(|err| (message, err))
+        }
+        Command::Reset(cmd) => {
+            let message = format!(
+                "Failed reset, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            match is_zip(&cmd.directory) {
+                true => reset_zip(cmd),
+                false => reset(cmd),
+            }
+            .map_err(|err| (message, err))
+        }
+        Command::Verify(cmd) => {
+            let message = format!(
+                "Failed verification, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            verify(cmd).map_err(|err| (message, err))
+        }
+        Command::ListStations(cmd) => {
+            let message = format!(
+                "Failed listing stations, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            list_stations(cmd).map_err(|err| (message, err))
+        }
+        Command::Snapshot(cmd) => {
+            let message = format!(
+                "Failed snapshot, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            snapshot(cmd).map_err(|err| (message, err))
+        }
+        Command::Info(cmd) => {
+            let message = format!(
+                "Failed info, directory: {}",
+                cmd.directory.to_string_lossy()
+            );
+
+            info(cmd).map_err(|err| (message, err))
+        }
+        Command::PreviewDistribution(cmd) => preview_distribution(*cmd)
+            .map_err(|err| ("Failed previewing distribution".to_owned(), err)),
+    };
 
-    match cli.command {
-        Command::Modify(cmd) => modify(cmd),
-        Command::Reset(cmd) => reset(cmd),
+    if let Err((message, err)) = result {
+        print_error(&message, &err);
+        process::exit(1);
     }
 }
@@ -0,0 +1,45 @@
+//! Names of the `_zsw` folder for the ways a folder can be given on the command line.
+
+use std::path::{Path, PathBuf};
+
+use zusischewe::copy_name;
+
+#[test]
+fn folder_name() {
+    assert_eq!(
+        copy_name(Path::new("scenario")).unwrap(),
+        Path::new("scenario_zsw")
+    );
+}
+
+#[test]
+fn trailing_separator_is_ignored() {
+    assert_eq!(
+        copy_name(Path::new("scenario/")).unwrap(),
+        Path::new("scenario_zsw")
+    );
+    assert_eq!(
+        copy_name(Path::new("./scenario/")).unwrap(),
+        Path::new("./scenario_zsw")
+    );
+}
+
+#[test]
+fn current_folder_is_resolved() {
+    // Tests run in the folder of the package.
+    let package = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .canonicalize()
+        .unwrap();
+    let mut expected = package.file_name().unwrap().to_os_string();
+    expected.push("_zsw");
+
+    assert_eq!(
+        copy_name(Path::new(".")).unwrap(),
+        package.with_file_name(expected)
+    );
+}
+
+#[test]
+fn root_has_no_name() {
+    assert!(copy_name(Path::new("/")).is_err());
+}
@@ -0,0 +1,31 @@
+//! Runs the info command of the binary on folders of fixtures in the temporary folder.
+
+use std::{fs, path::Path, process::Command};
+
+#[test]
+fn train_without_consist_is_counted() {
+    let dir = std::env::temp_dir().join(format!(
+        "zusischewe-info-test-{}-train_without_consist_is_counted",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    for name in ["locomotive.trn", "no_consist.trn"] {
+        fs::copy(fixtures.join(name), dir.join(name)).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("info")
+        .arg(&dir)
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("With locomotive: 1 of 2"));
+    assert!(stdout.contains("Without consist: 1"));
+}
@@ -0,0 +1,545 @@
+//! Runs `modify_file` on the trains in `tests/fixtures`: a locomotive-hauled train, a multiple unit, a freight train and a train starting at its first stop, the locomotive-hauled train with a decimal comma, the multiple unit without consist and a train encoded in Windows-1252.
+//!
+//! The golden test compares the written files with those in `tests/golden`. Run it with `ZUSISCHEWE_UPDATE_GOLDEN=1` to write them again after intended changes.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use zusischewe::{
+    modify_file, read_file, route, verify_file, write_file, xmltree::Element, ModifyParams,
+};
+
+const FIXTURES: [&str; 4] = [
+    "locomotive.trn",
+    "multiple_unit.trn",
+    "freight.trn",
+    "origin.trn",
+];
+
+/// Copies fixture `name` into a folder of its own for `test`, as tests run in parallel and modify the file.
+fn fixture(test: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zusischewe-test-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(name);
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+        &path,
+    )
+    .unwrap();
+
+    path
+}
+
+fn zug(path: &Path) -> Element {
+    read_file(path).unwrap().0.take_child("Zug").unwrap()
+}
+
+fn apbeschl(path: &Path) -> f32 {
+    zug(path).attributes["APBeschl"].parse().unwrap()
+}
+
+/// `attribute`, like `Ank` or `Abf`, of each `FahrplanEintrag`.
+fn times(path: &Path, attribute: &str) -> Vec<Option<String>> {
+    zug(path)
+        .children
+        .into_iter()
+        .filter_map(|node| node.as_element().cloned())
+        .filter(|element| element.name == "FahrplanEintrag")
+        .map(|entry| entry.attributes.get(attribute).cloned())
+        .collect()
+}
+
+/// Delays the entry by exactly `minutes`, without randomness.
+fn fixed_entry_delay(minutes: f32) -> ModifyParams {
+    ModifyParams {
+        min_entry_delay: Some(minutes),
+        max_entry_delay: Some(minutes),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn locomotive_uses_loc_multiplier() {
+    let path = fixture("locomotive_uses_loc_multiplier", "locomotive.trn");
+    let params = ModifyParams {
+        friction: 0.2,
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert!(report.changed);
+    assert_eq!(report.has_locomotive, Some(true));
+    assert_eq!(report.multiplier, 0.5);
+    assert_eq!(apbeschl(&path), 0.135);
+}
+
+#[test]
+fn unchanged_train_is_reported_unchanged() {
+    let path = fixture("unchanged_train_is_reported_unchanged", "locomotive.trn");
+    let original = fs::read_to_string(&path).unwrap();
+
+    let report = modify_file(&path, &ModifyParams::default(), 1).unwrap();
+
+    assert!(!report.changed);
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+}
+
+#[test]
+fn modifying_again_multiplies_original_acceleration() {
+    let path = fixture(
+        "modifying_again_multiplies_original_acceleration",
+        "locomotive.trn",
+    );
+    let params = ModifyParams {
+        friction: 0.2,
+        ..Default::default()
+    };
+
+    modify_file(&path, &params, 1).unwrap();
+    modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(apbeschl(&path), 0.135);
+    assert_eq!(zug(&path).attributes["APBeschlOrig"], "0.27");
+}
+
+#[test]
+fn accel_reduction_percent_ignores_friction() {
+    let path = fixture("accel_reduction_percent_ignores_friction", "locomotive.trn");
+    let params = ModifyParams {
+        friction: 0.2,
+        accel_reduction_percent: Some(20.0),
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(report.multiplier, 0.8);
+    assert!((apbeschl(&path) - 0.216).abs() < 1e-6);
+}
+
+#[test]
+fn ratio_model_interpolates_by_locomotives() {
+    let path = fixture("ratio_model_interpolates_by_locomotives", "locomotive.trn");
+    let params = ModifyParams {
+        friction: 0.2,
+        ratio_model: true,
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    // 2 locomotives of 19 vehicles.
+    assert_eq!(report.has_locomotive, Some(true));
+    assert!((report.multiplier - 0.2 / (0.25 + 0.15 * 17.0 / 19.0)).abs() < 1e-6);
+}
+
+#[test]
+fn decimal_comma_is_kept() {
+    let path = fixture("decimal_comma_is_kept", "decimal_comma.trn");
+    let params = ModifyParams {
+        friction: 0.2,
+        ..Default::default()
+    };
+
+    modify_file(&path, &params, 1).unwrap();
+
+    let zug = zug(&path);
+    assert_eq!(zug.attributes["APBeschl"], "0,135");
+    assert_eq!(zug.attributes["APBeschlOrig"], "0,27");
+}
+
+#[test]
+fn decimal_comma_verifies() {
+    let path = fixture("decimal_comma_verifies", "decimal_comma.trn");
+
+    verify_file(&path, None, &[], true).unwrap();
+
+    // A modified file also has the original acceleration with a decimal comma.
+    modify_file(
+        &path,
+        &ModifyParams {
+            friction: 0.2,
+            ..Default::default()
+        },
+        1,
+    )
+    .unwrap();
+    verify_file(&path, None, &[], true).unwrap();
+}
+
+#[test]
+fn dry_run_keeps_file() {
+    let path = fixture("dry_run_keeps_file", "locomotive.trn");
+    let original = fs::read(&path).unwrap();
+    let params = ModifyParams {
+        dry_run: true,
+        ..fixed_entry_delay(5.0)
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert!(report.changed);
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    let modified = report.modified.unwrap();
+    let entry = modified
+        .get_child("Zug")
+        .unwrap()
+        .get_child("FahrplanEintrag")
+        .unwrap();
+    assert_eq!(entry.attributes["Ank"], "2016-05-20 06:20:00");
+}
+
+#[test]
+fn jitter_rounded_away_keeps_file() {
+    let path = fixture("jitter_rounded_away_keeps_file", "locomotive.trn");
+    let original = fs::read(&path).unwrap();
+    // The departures with an arrival are on full minutes, so a second of jitter is rounded away.
+    let params = ModifyParams {
+        departure_jitter: 1,
+        round_to: 60,
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert!(!report.changed);
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn route_is_without_entry_delay_note() {
+    let path = fixture("route_is_without_entry_delay_note", "locomotive.trn");
+    let params = ModifyParams {
+        entry_delay_note: true,
+        ..fixed_entry_delay(5.0)
+    };
+
+    modify_file(&path, &params, 1).unwrap();
+
+    let (tree, _) = read_file(&path).unwrap();
+    assert_eq!(
+        tree.get_child("Zug").unwrap().attributes["Zuglauf"],
+        "München Hbf - Amsterdam CS (+5 min)"
+    );
+    assert_eq!(route(&tree), Some("München Hbf - Amsterdam CS"));
+}
+
+#[test]
+fn windows_1252_is_written_back_unchanged() {
+    let path = fixture("windows_1252_is_written_back_unchanged", "windows_1252.trn");
+    let original = fs::read(&path).unwrap();
+
+    // A modification changing nothing leaves the file as it is.
+    let report = modify_file(&path, &ModifyParams::default(), 1).unwrap();
+    assert!(!report.changed);
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    let (tree, format) = read_file(&path).unwrap();
+    write_file(&path, tree, &format).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn windows_1252_is_kept() {
+    let path = fixture("windows_1252_is_kept", "windows_1252.trn");
+
+    let report = modify_file(&path, &fixed_entry_delay(5.0), 1).unwrap();
+    assert!(report.changed);
+
+    let written = fs::read(&path).unwrap();
+    assert!(std::str::from_utf8(&written).is_err());
+    assert!(written.starts_with(b"<?xml version=\"1.0\" encoding=\"Windows-1252\"?>\r\n"));
+    // `Köln Hbf`, with the umlaut as a single byte.
+    assert!(written.windows(8).any(|window| window == b"K\xf6ln Hbf"));
+
+    let zug = zug(&path);
+    let station = zug
+        .get_child("FahrplanEintrag")
+        .unwrap()
+        .attributes
+        .get("Betrst")
+        .unwrap();
+    assert_eq!(station, "Köln Hbf");
+}
+
+#[test]
+fn train_without_consist_verifies() {
+    let path = fixture("train_without_consist_verifies", "no_consist.trn");
+
+    verify_file(&path, None, &[], true).unwrap();
+}
+
+#[test]
+fn invalid_times_name_their_attribute() {
+    let path = fixture("invalid_times_name_their_attribute", "multiple_unit.trn");
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::write(
+        &path,
+        contents.replace("Ank=\"2016-05-20 06:10:00\"", "Ank=\"06:10\""),
+    )
+    .unwrap();
+
+    let err = verify_file(&path, None, &[], true).unwrap_err();
+
+    assert_eq!(err.to_string(), "parsing arrival time `06:10`");
+}
+
+#[test]
+fn multiple_unit_uses_mu_multiplier() {
+    let path = fixture("multiple_unit_uses_mu_multiplier", "multiple_unit.trn");
+    let params = ModifyParams {
+        friction: 0.2,
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(report.has_locomotive, Some(false));
+    assert_eq!(report.multiplier, 0.8);
+    assert!((apbeschl(&path) - 0.72).abs() < 1e-6);
+}
+
+#[test]
+fn locomotive_substring_extends_detection() {
+    let path = fixture(
+        "locomotive_substring_extends_detection",
+        "multiple_unit.trn",
+    );
+    let params = ModifyParams {
+        friction: 0.2,
+        locomotive_substring: vec!["TRIEBWAGEN".to_owned()],
+        ..Default::default()
+    };
+
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(report.has_locomotive, Some(true));
+    assert_eq!(report.multiplier, 0.5);
+}
+
+#[test]
+fn entry_delay_moves_only_entry() {
+    let path = fixture("entry_delay_moves_only_entry", "locomotive.trn");
+    let (arrivals, departures) = (times(&path, "Ank"), times(&path, "Abf"));
+
+    let report = modify_file(&path, &fixed_entry_delay(5.0), 1).unwrap();
+
+    assert_eq!(report.entry_delay_seconds, 300);
+
+    let mut expected = arrivals;
+    expected[0] = Some("2016-05-20 06:20:00".to_owned());
+
+    assert_eq!(times(&path, "Ank"), expected);
+    assert_eq!(times(&path, "Abf"), departures);
+}
+
+#[test]
+fn times_without_seconds_keep_their_precision() {
+    let path = fixture(
+        "times_without_seconds_keep_their_precision",
+        "multiple_unit.trn",
+    );
+    fs::write(
+        &path,
+        fs::read_to_string(&path).unwrap().replace(":00\"", "\""),
+    )
+    .unwrap();
+
+    let params = ModifyParams {
+        propagate_entry_delay: true,
+        ..fixed_entry_delay(2.0)
+    };
+    modify_file(&path, &params, 1).unwrap();
+
+    // Times with seconds keep them.
+    assert_eq!(
+        times(&path, "Ank"),
+        [
+            Some("2016-05-20 06:07"),
+            Some("2016-05-20 06:12"),
+            Some("2016-05-20 06:17:30"),
+            None,
+            Some("2016-05-20 06:31"),
+            Some("2016-05-20 06:51:30"),
+        ]
+        .map(|time| time.map(str::to_owned))
+    );
+}
+
+#[test]
+fn propagated_entry_delay_moves_all_stops() {
+    let path = fixture(
+        "propagated_entry_delay_moves_all_stops",
+        "multiple_unit.trn",
+    );
+    let params = ModifyParams {
+        propagate_entry_delay: true,
+        ..fixed_entry_delay(2.0)
+    };
+
+    modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(
+        times(&path, "Ank"),
+        [
+            Some("2016-05-20 06:07:00"),
+            Some("2016-05-20 06:12:00"),
+            Some("2016-05-20 06:17:30"),
+            None,
+            Some("2016-05-20 06:31:00"),
+            Some("2016-05-20 06:51:30"),
+        ]
+        .map(|time| time.map(str::to_owned))
+    );
+    assert_eq!(
+        times(&path, "Abf"),
+        [
+            Some("2016-05-20 06:10:00"),
+            Some("2016-05-20 06:13:00"),
+            Some("2016-05-20 06:18:30"),
+            Some("2016-05-20 06:26:00"),
+            Some("2016-05-20 06:32:00"),
+            None,
+        ]
+        .map(|time| time.map(str::to_owned))
+    );
+}
+
+#[test]
+fn origin_departure_delays_first_departure() {
+    let path = fixture("origin_departure_delays_first_departure", "origin.trn");
+
+    modify_file(&path, &fixed_entry_delay(5.0), 1).unwrap();
+
+    assert_eq!(times(&path, "Ank")[0], None);
+    assert_eq!(
+        times(&path, "Abf")[0].as_deref(),
+        Some("2016-05-20 06:31:00")
+    );
+}
+
+#[test]
+fn skip_window_leaves_train_as_it_is() {
+    let path = fixture("skip_window_leaves_train_as_it_is", "locomotive.trn");
+    let original = fs::read(&path).unwrap();
+
+    // The train enters at 06:15.
+    let params = ModifyParams {
+        friction: 0.2,
+        skip_window: vec!["22:00-06:30".parse().unwrap()],
+        ..fixed_entry_delay(5.0)
+    };
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert!(report.skipped);
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn sim_start_leaves_running_trains_as_they_are() {
+    let path = fixture(
+        "sim_start_leaves_running_trains_as_they_are",
+        "locomotive.trn",
+    );
+    let original = fs::read(&path).unwrap();
+
+    let params = ModifyParams {
+        sim_start: Some(zusischewe::parse_date_time("2016-05-20 06:30").unwrap()),
+        ..fixed_entry_delay(5.0)
+    };
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert!(report.skipped);
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn freight_uses_freight_departures_delay_factor() {
+    let path = fixture(
+        "freight_uses_freight_departures_delay_factor",
+        "freight.trn",
+    );
+    let departures = times(&path, "Abf");
+
+    let params = ModifyParams {
+        departures_delay_factor: 2.0,
+        ..Default::default()
+    };
+    let report = modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(report.departures_delayed, 0);
+    assert_eq!(times(&path, "Abf"), departures);
+
+    // The 12 minute stop at Leverkusen Mitte doubles, limited by the departures max delay of 6 minutes.
+    let params = ModifyParams {
+        freight_departures_delay_factor: 2.0,
+        ..Default::default()
+    };
+    modify_file(&path, &params, 1).unwrap();
+
+    assert_eq!(
+        times(&path, "Abf")[2].as_deref(),
+        Some("2016-05-20 07:10:00")
+    );
+}
+
+#[test]
+fn same_seed_gives_same_file() {
+    let params = ModifyParams {
+        bell_mean: Some(5.0),
+        delay_probability: Some(0.5),
+        ..Default::default()
+    };
+
+    let first = fixture("same_seed_gives_same_file_1", "locomotive.trn");
+    let second = fixture("same_seed_gives_same_file_2", "locomotive.trn");
+
+    modify_file(&first, &params, 7).unwrap();
+    modify_file(&second, &params, 7).unwrap();
+
+    assert_eq!(fs::read(first).unwrap(), fs::read(second).unwrap());
+}
+
+#[test]
+fn golden_files() {
+    let params = ModifyParams {
+        friction: 0.2,
+        bell_mean: Some(4.0),
+        bell_deviation: 3.0,
+        propagate_entry_delay: true,
+        recovery_per_stop: 20,
+        departures_delay_factor: 1.5,
+        freight_departures_delay_factor: 1.2,
+        round_to: 30,
+        ..Default::default()
+    };
+
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("ZUSISCHEWE_UPDATE_GOLDEN").is_some();
+
+    for name in FIXTURES {
+        let path = fixture("golden_files", name);
+        modify_file(&path, &params, 1).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        let golden = golden_dir.join(name);
+
+        if update {
+            fs::write(&golden, &written).unwrap();
+        }
+
+        assert_eq!(
+            written,
+            fs::read_to_string(&golden).unwrap(),
+            "{name} differs from its golden file"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Runs the reset command of the binary on folders in the temporary folder.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// An empty folder named `Scenario` for `test`, in a folder of its own as tests run in parallel.
+fn scenario(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "zusischewe-reset-test-{}-{test}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+
+    let scenario = dir.join("Scenario");
+    fs::create_dir_all(&scenario).unwrap();
+
+    scenario
+}
+
+fn reset(directory: &Path) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("reset")
+        .arg(directory)
+        .arg("--force")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn empty_backup_is_refused() {
+    let scenario = scenario("empty_backup_is_refused");
+    fs::write(scenario.join("train.trn"), "modified").unwrap();
+    fs::create_dir(scenario.with_file_name("Scenario_zsw")).unwrap();
+
+    let output = reset(&scenario);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("contains no '.trn' files"));
+    assert_eq!(
+        fs::read_to_string(scenario.join("train.trn")).unwrap(),
+        "modified"
+    );
+}
+
+#[test]
+fn backup_is_restored() {
+    let scenario = scenario("backup_is_restored");
+    fs::write(scenario.join("train.trn"), "modified").unwrap();
+    let backup = scenario.with_file_name("Scenario_zsw");
+    fs::create_dir(&backup).unwrap();
+    fs::write(backup.join("train.trn"), "original").unwrap();
+
+    let output = reset(&scenario);
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(scenario.join("train.trn")).unwrap(),
+        "original"
+    );
+    assert!(!backup.exists());
+}
+
+#[test]
+fn backup_folder_inside_folder_is_refused() {
+    let scenario = scenario("backup_folder_inside_folder_is_refused");
+    fs::write(scenario.join("train.trn"), "modified").unwrap();
+    let backup = scenario.join("bak");
+    fs::create_dir(&backup).unwrap();
+    fs::write(backup.join("train.trn"), "original").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("reset")
+        .arg(&scenario)
+        .arg("--backup-dir")
+        .arg(&backup)
+        .arg("--force")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is inside"));
+    assert_eq!(
+        fs::read_to_string(scenario.join("train.trn")).unwrap(),
+        "modified"
+    );
+    assert!(backup.join("train.trn").exists());
+}
+
+#[test]
+fn dry_run_lists_compressed_backup() {
+    let scenario = scenario("dry_run_lists_compressed_backup");
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/locomotive.trn"),
+        scenario.join("locomotive.trn"),
+    )
+    .unwrap();
+
+    let modify = Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("modify")
+        .arg(&scenario)
+        .arg("--backup-compress")
+        .output()
+        .unwrap();
+    assert!(modify.status.success());
+
+    // Changed after modifying, which a dry run does not refuse.
+    fs::write(scenario.join("notes.txt"), "new").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("reset")
+        .arg(&scenario)
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Would delete:    notes.txt"));
+    // The default modification leaves the train as it is, found in the archive.
+    assert!(stdout.contains("0 restored and 0 overwritten, 1 are unchanged"));
+    assert!(scenario.with_file_name("Scenario_zsw.tar.zst").exists());
+    assert!(!scenario.with_file_name("Scenario_zsw").exists());
+}
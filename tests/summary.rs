@@ -0,0 +1,35 @@
+//! Runs the modify command of the binary with `--summary-only` on a folder in the temporary folder.
+
+use std::{fs, path::Path, process::Command};
+
+#[test]
+fn summary_changes_no_files() {
+    let dir = std::env::temp_dir().join(format!(
+        "zusischewe-summary-test-{}-summary_changes_no_files",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+
+    let scenario = dir.join("Scenario");
+    fs::create_dir_all(&scenario).unwrap();
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/locomotive.trn"),
+        scenario.join("locomotive.trn"),
+    )
+    .unwrap();
+    let original = fs::read(scenario.join("locomotive.trn")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zusischewe"))
+        .arg("modify")
+        .arg(&scenario)
+        .arg("--summary-only")
+        .args(["--min-entry-delay", "5", "--max-entry-delay", "5"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Delayed entry of 1 of 1 trains"));
+    assert_eq!(fs::read(scenario.join("locomotive.trn")).unwrap(), original);
+    assert_eq!(fs::read_dir(&scenario).unwrap().count(), 1);
+    assert!(!dir.join("Scenario_zsw").exists());
+}